@@ -0,0 +1,49 @@
+//  Copyright (c) 2019 Alain Brenzikofer
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+//! # Encointer Currencies Runtime API
+//!
+//! read-only queries of the encointer currencies module for wallets and tooling
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use encointer_currencies::{
+    BalanceType, CurrencyIdentifier, Demurrage, GeoError, Location, LocationIndexType,
+    LocationReport, LocationValidationIssue,
+};
+use rstd::prelude::*;
+use sp_runtime::Percent;
+
+sp_api::decl_runtime_apis! {
+    pub trait CurrenciesApi {
+        /// dry-run of the location checks of `new_currency`
+        fn validate_locations(loc: Vec<Location>) -> Vec<LocationValidationIssue>;
+        /// closest location to `loc` that `new_currency` would accept, if any within 10km
+        fn suggest_nearest_valid_location(loc: Location) -> Option<Location>;
+        /// pairwise distances and solar trip times of all locations of a currency
+        fn location_report(cid: CurrencyIdentifier) -> Result<LocationReport, GeoError>;
+        /// number of participants a location of a currency can host, 0 if it doesn't exist
+        fn location_capacity(cid: CurrencyIdentifier, location_index: LocationIndexType) -> u32;
+        /// number of participants all locations of a currency can host together
+        fn total_capacity(cid: CurrencyIdentifier) -> u32;
+        /// per-block demurrage of a currency
+        fn demurrage_per_block(cid: CurrencyIdentifier) -> Demurrage;
+        /// per-block demurrage which halves balances every `days`
+        fn demurrage_from_half_life_days(days: u32) -> Option<Demurrage>;
+        /// per-block demurrage under which balances lose `rate` of their value per year
+        fn demurrage_from_annual_rate(rate: Percent) -> Option<Demurrage>;
+        /// money supply of a currency with demurrage applied up to the current block
+        fn total_issuance(cid: CurrencyIdentifier) -> BalanceType;
+    }
+}
@@ -26,49 +26,571 @@ use support::{
     decl_event, decl_module, decl_storage, decl_error,
     dispatch::DispatchResult,
     ensure,
-    storage::{StorageMap, StorageValue},
+    storage::{StorageDoubleMap, StorageMap, StorageValue},
+    traits::{Get, LockIdentifier},
+    weights::{DispatchInfo, Weight},
 };
 use system::ensure_signed;
 
-use rstd::prelude::*;
+use rstd::{collections::btree_map::BTreeMap, prelude::*};
+use core::{fmt, marker::PhantomData, str::FromStr};
 
 use codec::{Decode, Encode};
 pub use fixed::traits::{LossyFrom, LossyInto};
-use fixed::transcendental::{asin, cos, powi, sin, sqrt};
+use fixed::transcendental::{asin, cos, exp, powi, sin, sqrt};
 use fixed::types::{I32F0, I32F32, U0F64, I64F64};
 use primitives::H256;
-use runtime_io::{
-    hashing::blake2_256,
-    misc::{print_hex, print_utf8},
+use runtime_io::hashing::blake2_256;
+use sp_runtime::{
+    traits::{AccountIdConversion, Convert, SignedExtension, UniqueSaturatedInto},
+    transaction_validity::{
+        InvalidTransaction, TransactionValidity, TransactionValidityError, ValidTransaction,
+    },
+    ModuleId, Percent,
 };
 
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    /// [m] minimum distance between any two locations, in addition to the solar trip time rule
+    type MinLocationDistanceM: Get<u32>;
+    /// number of participants a location can host unless its bootstrappers specified otherwise
+    type DefaultLocationCapacity: Get<u32>;
+    /// [ms] expected time between blocks, used to derive per-block demurrage from durations
+    type ExpectedBlockTimeMs: Get<u64>;
+    /// native fee per transaction, when paying fees in community currency
+    type TransactionBaseFee: Get<u64>;
+    /// native fee per byte of a transaction, when paying fees in community currency
+    type TransactionByteFee: Get<u64>;
+    /// native fee for the weight of a call, when paying fees in community currency
+    type WeightToFee: Convert<Weight, u64>;
+    /// receives fees paid in community currency
+    type OnFeePaid: OnCommunityFeePaid;
 }
 
 pub type CurrencyIndexType = u32;
 pub type LocationIndexType = u32;
 pub type Degree = I32F32;
 pub type Demurrage = I64F64;
+pub type BalanceType = I64F64;
 
-// Location in lat/lon. Fixpoint value in degree with 8 decimal bits and 24 fractional bits
+// Location in lat/lon. Fixpoint value in degree with 32 integer bits and 32 fractional bits.
+// Canonical locations only use the upper 23 fractional bits, which is a precision of ~1.3cm
 #[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
 pub struct Location {
     pub lat: Degree,
     pub lon: Degree,
 }
+
+impl Location {
+    /// validating constructor. Coordinates must be canonical and within range
+    pub fn new(lat: Degree, lon: Degree) -> Result<Location, LocationError> {
+        let loc = Location { lat, lon };
+        loc.validate()?;
+        Ok(loc)
+    }
+
+    /// from decimal degrees, rounded to canonical precision
+    #[cfg(feature = "std")]
+    pub fn from_decimal_degrees(lat: f64, lon: f64) -> Result<Location, LocationError> {
+        if !lat.is_finite() || lat.abs() > 90.0 {
+            return Err(LocationError::InvalidLatitude);
+        }
+        if !lon.is_finite() || lon.abs() > 180.0 {
+            return Err(LocationError::InvalidLongitude);
+        }
+        Location {
+            lat: Degree::from_num(lat),
+            lon: Degree::from_num(lon),
+        }
+        .canonicalize()
+        .checked()
+    }
+
+    /// from millionths of a degree, rounded to canonical precision
+    pub fn from_microdegrees(lat: i32, lon: i32) -> Result<Location, LocationError> {
+        if lat.unsigned_abs() > 90_000_000 {
+            return Err(LocationError::InvalidLatitude);
+        }
+        if lon.unsigned_abs() > 180_000_000 {
+            return Err(LocationError::InvalidLongitude);
+        }
+        let micro = Degree::from_num(1_000_000);
+        Location {
+            lat: Degree::from_num(lat) / micro,
+            lon: Degree::from_num(lon) / micro,
+        }
+        .canonicalize()
+        .checked()
+    }
+
+    /// `Ok(())` if the location is canonical and within range
+    pub fn validate(&self) -> Result<(), LocationError> {
+        if self.lat > NORTH_POLE.lat || self.lat < SOUTH_POLE.lat {
+            return Err(LocationError::InvalidLatitude);
+        }
+        if self.lon > DATELINE_LON || self.lon < -DATELINE_LON {
+            return Err(LocationError::InvalidLongitude);
+        }
+        if !self.is_canonical() {
+            return Err(LocationError::NonCanonical);
+        }
+        Ok(())
+    }
+
+    fn checked(self) -> Result<Location, LocationError> {
+        self.validate().map(|_| self)
+    }
+
+    /// geohash of this location with `precision` characters, at most `MAX_GEOHASH_PRECISION`
+    pub fn to_geohash(&self, precision: usize) -> Vec<u8> {
+        let two = Degree::from_num(2);
+        let mut lat_range = (SOUTH_POLE.lat, NORTH_POLE.lat);
+        let mut lon_range = (-DATELINE_LON, DATELINE_LON);
+        let mut hash = Vec::with_capacity(precision.min(MAX_GEOHASH_PRECISION));
+        // bits alternate between longitude and latitude, starting with longitude
+        let mut is_lon = true;
+        for _ in 0..precision.min(MAX_GEOHASH_PRECISION) {
+            let mut idx = 0;
+            for _ in 0..5 {
+                let (range, value) = if is_lon {
+                    (&mut lon_range, self.lon)
+                } else {
+                    (&mut lat_range, self.lat)
+                };
+                let mid = (range.0 + range.1) / two;
+                idx <<= 1;
+                if value >= mid {
+                    idx |= 1;
+                    range.0 = mid;
+                } else {
+                    range.1 = mid;
+                }
+                is_lon = !is_lon;
+            }
+            hash.push(GEOHASH_ALPHABET[idx]);
+        }
+        hash
+    }
+
+    /// center of the geohash cell, rounded to canonical precision
+    pub fn from_geohash(hash: &str) -> Result<Location, LocationError> {
+        if hash.is_empty() || hash.len() > MAX_GEOHASH_PRECISION {
+            return Err(LocationError::InvalidGeohash);
+        }
+        let two = Degree::from_num(2);
+        let mut lat_range = (SOUTH_POLE.lat, NORTH_POLE.lat);
+        let mut lon_range = (-DATELINE_LON, DATELINE_LON);
+        let mut is_lon = true;
+        for c in hash.bytes() {
+            let idx = GEOHASH_ALPHABET
+                .iter()
+                .position(|a| *a == c.to_ascii_lowercase())
+                .ok_or(LocationError::InvalidGeohash)?;
+            for bit in (0..5).rev() {
+                let range = if is_lon {
+                    &mut lon_range
+                } else {
+                    &mut lat_range
+                };
+                let mid = (range.0 + range.1) / two;
+                if idx >> bit & 1 == 1 {
+                    range.0 = mid;
+                } else {
+                    range.1 = mid;
+                }
+                is_lon = !is_lon;
+            }
+        }
+        Location {
+            lat: (lat_range.0 + lat_range.1) / two,
+            lon: (lon_range.0 + lon_range.1) / two,
+        }
+        .canonicalize()
+        .checked()
+    }
+
+    /// true if both coordinates are multiples of the canonical precision
+    pub fn is_canonical(&self) -> bool {
+        self.lat.to_bits() & NON_CANONICAL_BITS_MASK == 0
+            && self.lon.to_bits() & NON_CANONICAL_BITS_MASK == 0
+    }
+
+    /// rounds both coordinates to the nearest multiple of the canonical precision
+    pub fn canonicalize(&self) -> Location {
+        Location {
+            lat: canonical_degree(self.lat),
+            lon: canonical_degree(self.lon),
+        }
+    }
+}
+
+fn canonical_degree(d: Degree) -> Degree {
+    let half = (NON_CANONICAL_BITS_MASK + 1) >> 1;
+    Degree::from_bits(d.to_bits().saturating_add(half) & !NON_CANONICAL_BITS_MASK)
+}
+
+/// parses `"<lat>,<lon>"` in decimal degrees, e.g. `"47.3769,8.5417"`, rounded to canonical precision
+impl FromStr for Location {
+    type Err = LocationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut coords = s.split(',');
+        let (lat, lon) = match (coords.next(), coords.next(), coords.next()) {
+            (Some(lat), Some(lon), None) => (lat.trim(), lon.trim()),
+            _ => return Err(LocationError::InvalidFormat),
+        };
+        Location {
+            lat: lat.parse().map_err(|_| LocationError::InvalidLatitude)?,
+            lon: lon.parse().map_err(|_| LocationError::InvalidLongitude)?,
+        }
+        .canonicalize()
+        .checked()
+    }
+}
+
+/// formats as `"<lat>,<lon>"` in decimal degrees, such that parsing yields the same location
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.lat, self.lon)
+    }
+}
+
+/// reason why a `Location` could not be constructed
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LocationError {
+    /// latitude is not a number within [-90°, 90°]
+    InvalidLatitude,
+    /// longitude is not a number within [-180°, 180°]
+    InvalidLongitude,
+    /// coordinates carry more precision than canonical
+    NonCanonical,
+    /// input is not of the form `"<lat>,<lon>"`
+    InvalidFormat,
+    /// input is not a geohash of at most `MAX_GEOHASH_PRECISION` characters
+    InvalidGeohash,
+}
+
 pub type CurrencyIdentifier = H256;
 
+/// reason why a location can't be part of a new currency
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LocationRejectionReason {
+    /// coordinates out of range or not canonical
+    InvalidCoordinate,
+    /// location is too close to a pole
+    NearPole,
+    /// minimum solar trip time violated towards the location with this index of the same set
+    TooCloseToSibling(LocationIndexType),
+    /// minimum solar trip time violated towards a location of another currency
+    TooCloseToCurrency(CurrencyIdentifier, LocationIndexType),
+    /// closer than `MinLocationDistanceM` to the location with this index of the same set
+    BelowMinimumDistanceToSibling(LocationIndexType),
+    /// closer than `MinLocationDistanceM` to a location of another currency
+    BelowMinimumDistanceToCurrency(CurrencyIdentifier, LocationIndexType),
+    /// location lies within the region of another currency
+    InsideForeignRegion(CurrencyIdentifier),
+    /// fixed-point computation of distances or trip times failed
+    GeoComputationFailed,
+}
+
+impl LocationRejectionReason {
+    /// the other currency a location conflicts with, if any
+    pub fn cid_conflict(&self) -> Option<CurrencyIdentifier> {
+        match self {
+            LocationRejectionReason::TooCloseToCurrency(cid, _)
+            | LocationRejectionReason::BelowMinimumDistanceToCurrency(cid, _)
+            | LocationRejectionReason::InsideForeignRegion(cid) => Some(*cid),
+            _ => None,
+        }
+    }
+}
+
+/// an issue with the location at `location_index` of a proposed set of locations
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LocationValidationIssue {
+    pub location_index: LocationIndexType,
+    pub reason: LocationRejectionReason,
+}
+
+/// failure of a fixed-point geometry computation
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GeoError {
+    /// a transcendental function did not yield a result for its argument
+    TranscendentalFunctionFailed,
+    /// an intermediate result exceeded the range of its fixed-point type
+    Overflow,
+}
+
+/// the location of another currency with the smallest solar trip time to a location
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ForeignLocationProximity {
+    pub cid: CurrencyIdentifier,
+    pub location_index: LocationIndexType,
+    pub distance_m: u32,
+    pub solar_trip_time_s: i32,
+}
+
+/// pairwise distances and solar trip times between all locations of a currency
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
+pub struct LocationReport {
+    /// `distances_m[i][j]` is the distance from location `i` to location `j`
+    pub distances_m: Vec<Vec<u32>>,
+    /// `solar_trip_times_s[i][j]` is the solar trip time from location `i` to location `j`
+    pub solar_trip_times_s: Vec<Vec<i32>>,
+    /// per location, the foreign location with the smallest solar trip time, if any
+    pub closest_foreign: Vec<Option<ForeignLocationProximity>>,
+    /// all solar trip times between different locations must be at least this
+    pub min_solar_trip_time_s: i32,
+    /// all distances between different locations must be at least this
+    pub min_location_distance_m: u32,
+}
+
+/// accessibility features of a meetup venue, combined as bit flags
+pub type AccessibilityFlags = u32;
+pub const ACCESSIBLE_BY_WHEELCHAIR: AccessibilityFlags = 1 << 0;
+pub const ACCESSIBLE_BY_PUBLIC_TRANSPORT: AccessibilityFlags = 1 << 1;
+pub const VENUE_INDOORS: AccessibilityFlags = 1 << 2;
+
+/// venue information of a meetup location for display in wallets
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
+pub struct LocationMetadataType {
+    /// at most `MAX_VENUE_NAME_LEN` bytes
+    pub venue_name_utf8: Vec<u8>,
+    /// at most `MAX_LOCATION_DESCRIPTION_LEN` bytes
+    pub description_utf8: Vec<u8>,
+    pub accessibility: AccessibilityFlags,
+    /// 0 if unspecified, in which case `DefaultLocationCapacity` applies
+    pub max_participants: u32,
+}
+
 #[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
 pub struct CurrencyPropertiesType {
     pub name_utf8: Vec<u8>,
     pub demurrage_per_block: Demurrage,
+    /// amount issued to each participant of a ceremony
+    pub nominal_income: BalanceType,
+}
+
+impl CurrencyPropertiesType {
+    /// properties of a currency whose balances halve every `half_life_days`.
+    /// `None` if the half-life is shorter than a block
+    pub fn from_half_life_days(
+        name_utf8: Vec<u8>,
+        half_life_days: u32,
+        block_time_ms: u64,
+    ) -> Option<Self> {
+        Some(CurrencyPropertiesType {
+            name_utf8,
+            demurrage_per_block: demurrage_from_half_life(blocks_per_days(
+                half_life_days,
+                block_time_ms,
+            )?)?,
+            nominal_income: DEFAULT_NOMINAL_INCOME,
+        })
+    }
+
+    /// properties of a currency whose balances lose `annual_rate` of their value per year.
+    /// `None` for a rate of 100%
+    pub fn from_annual_rate(
+        name_utf8: Vec<u8>,
+        annual_rate: Percent,
+        block_time_ms: u64,
+    ) -> Option<Self> {
+        Some(CurrencyPropertiesType {
+            name_utf8,
+            demurrage_per_block: demurrage_from_annual_rate(annual_rate, block_time_ms)?,
+            nominal_income: DEFAULT_NOMINAL_INCOME,
+        })
+    }
+}
+
+/// balance of an account in a currency as of block `last_update`.
+/// Demurrage since then is applied lazily whenever the balance is read or written.
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct BalanceEntry<BlockNumber> {
+    pub principal: BalanceType,
+    pub last_update: BlockNumber,
+}
+
+/// restricts withdrawals from a balance, decaying along with it
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct BalanceLock<BlockNumber> {
+    pub id: LockIdentifier,
+    pub amount: BalanceEntry<BlockNumber>,
+}
+
+/// receives fees paid in community currency, which have already been withdrawn from the
+/// payer and the total issuance. `()` burns them
+pub trait OnCommunityFeePaid {
+    fn on_fee_paid(cid: &CurrencyIdentifier, fee: BalanceType);
+}
+
+impl OnCommunityFeePaid for () {
+    fn on_fee_paid(_cid: &CurrencyIdentifier, _fee: BalanceType) {}
+}
+
+/// mints community currency. Implemented by this module for other modules, e.g. ceremonies,
+/// to issue income to the participants of a ceremony
+pub trait CurrencyIssuer<AccountId> {
+    /// amount each participant of a ceremony of `cid` receives
+    fn nominal_income(cid: &CurrencyIdentifier) -> BalanceType;
+    /// credit `amount` of `cid` to each of `beneficiaries`. Either all or none are credited
+    fn issue(
+        cid: &CurrencyIdentifier,
+        beneficiaries: &[AccountId],
+        amount: BalanceType,
+    ) -> DispatchResult;
+}
+
+/// generic access to balances in multiple currencies for other modules, e.g. a DEX or treasury.
+/// Modelled after `MultiCurrency` of orml_traits
+pub trait MultiCurrency<AccountId> {
+    type CurrencyId;
+    type Balance;
+
+    /// sum of all balances in `currency_id`
+    fn total_issuance(currency_id: Self::CurrencyId) -> Self::Balance;
+    /// balance of `who` in `currency_id`
+    fn free_balance(currency_id: Self::CurrencyId, who: &AccountId) -> Self::Balance;
+    /// move `amount` from `from` to `to`
+    fn transfer(
+        currency_id: Self::CurrencyId,
+        from: &AccountId,
+        to: &AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult;
+    /// add `amount` to the balance of `who`, increasing the total issuance
+    fn deposit(currency_id: Self::CurrencyId, who: &AccountId, amount: Self::Balance)
+        -> DispatchResult;
+    /// remove `amount` from the balance of `who`, decreasing the total issuance
+    fn withdraw(currency_id: Self::CurrencyId, who: &AccountId, amount: Self::Balance)
+        -> DispatchResult;
+}
+
+/// reservable balances, modelled after `MultiReservableCurrency` of orml_traits.
+/// Reserved balances count towards the total issuance and decay like free balances
+pub trait MultiReservableCurrency<AccountId>: MultiCurrency<AccountId> {
+    /// reserved balance of `who` in `currency_id`
+    fn reserved_balance(currency_id: Self::CurrencyId, who: &AccountId) -> Self::Balance;
+    /// move `amount` from the free to the reserved balance of `who`
+    fn reserve(currency_id: Self::CurrencyId, who: &AccountId, amount: Self::Balance)
+        -> DispatchResult;
+    /// move up to `amount` from the reserved to the free balance of `who`.
+    /// Returns the amount which could not be unreserved
+    fn unreserve(currency_id: Self::CurrencyId, who: &AccountId, amount: Self::Balance)
+        -> Self::Balance;
+}
+
+/// lockable balances, modelled after `MultiLockableCurrency` of orml_traits.
+/// The free balance can't be reduced below the largest lock, which decays like the balance
+pub trait MultiLockableCurrency<AccountId>: MultiCurrency<AccountId> {
+    /// create or replace the lock `lock_id` on the free balance of `who`
+    fn set_lock(
+        lock_id: LockIdentifier,
+        currency_id: Self::CurrencyId,
+        who: &AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult;
+    /// remove the lock `lock_id` from the free balance of `who`, if any
+    fn remove_lock(lock_id: LockIdentifier, currency_id: Self::CurrencyId, who: &AccountId);
+}
+
+/// `balance * exp(-demurrage * elapsed_blocks)`. Non-positive demurrage leaves the balance unchanged
+pub fn apply_demurrage(
+    balance: BalanceType,
+    demurrage: Demurrage,
+    elapsed_blocks: u32,
+) -> BalanceType {
+    if demurrage <= Demurrage::from_num(0) {
+        return balance;
+    }
+    let exponent = match demurrage.checked_mul(Demurrage::from_num(elapsed_blocks)) {
+        Some(e) if e < Demurrage::from_num(MAX_DEMURRAGE_EXPONENT) => e,
+        _ => return BalanceType::from_num(0),
+    };
+    match exp::<BalanceType, BalanceType>(-exponent) {
+        Ok(factor) => balance * factor,
+        Err(_) => BalanceType::from_num(0),
+    }
+}
+
+/// per-block demurrage which halves balances every `blocks`. `None` for 0 blocks
+pub fn demurrage_from_half_life(blocks: u64) -> Option<Demurrage> {
+    let blocks = Demurrage::checked_from_num(blocks)?;
+    LN_2.checked_div(blocks)
+}
+
+/// per-block demurrage under which balances lose `annual_rate` of their value per year,
+/// i.e. `-ln(1 - annual_rate) / blocks_per_year`. `None` for a rate of 100%
+pub fn demurrage_from_annual_rate(annual_rate: Percent, block_time_ms: u64) -> Option<Demurrage> {
+    let rate = Demurrage::from_num(annual_rate.deconstruct()) / Demurrage::from_num(100);
+    if rate == Demurrage::from_num(0) {
+        return Some(rate);
+    }
+    let blocks = Demurrage::checked_from_num(blocks_per_days(DAYS_PER_YEAR, block_time_ms)?)?;
+    neg_ln(Demurrage::from_num(1) - rate)?.checked_div(blocks)
+}
+
+/// number of blocks produced in `days`, rounded down. `None` if less than one block
+pub fn blocks_per_days(days: u32, block_time_ms: u64) -> Option<u64> {
+    let blocks = u64::from(days)
+        .checked_mul(MS_PER_DAY)?
+        .checked_div(block_time_ms)?;
+    if blocks == 0 {
+        return None;
+    }
+    Some(blocks)
+}
+
+/// `-ln(x)` for `0 < x <= 1`, found by Newton's method on `exp(-y) = x`.
+/// The iteration converges monotonically from below, as `exp(-y)` is convex
+fn neg_ln(x: I64F64) -> Option<I64F64> {
+    if x <= I64F64::from_num(0) || x > I64F64::from_num(1) {
+        return None;
+    }
+    let one = I64F64::from_num(1);
+    // -ln(x) >= 1 - x, so we start below the root
+    let mut y = one - x;
+    for _ in 0..NEG_LN_ITERATIONS {
+        let e = exp::<I64F64, I64F64>(y).ok()?;
+        let next = y + one - x.checked_mul(e)?;
+        if next == y {
+            break;
+        }
+        y = next;
+    }
+    Some(y)
+}
+
+/// number of blocks after which balances are halved by `demurrage`, rounded.
+/// `None` for non-positive or too small demurrage
+pub fn half_life_from_demurrage(demurrage: Demurrage) -> Option<u64> {
+    if demurrage <= Demurrage::from_num(0) {
+        return None;
+    }
+    let blocks = LN_2.checked_div(demurrage)?;
+    Some(blocks.round().to_num::<u64>())
 }
 
 const MAX_SPEED_MPS: i32 = 83; // [m/s] max speed over ground of adversary
 const MIN_SOLAR_TRIP_TIME_S: i32 = 1; // [s] minimum adversary trip time between two locations measured in local (solar) time.
+const SOLAR_SECONDS_PER_DEGREE: i32 = 240; // [s/°] 24h * 3600s / 360°
+
+const POLE_DISTANCE_M: u32 = 1_000_000; // meetups may not be closer to poles than this
+
+// suggest_nearest_valid_location searches rings of candidates around the rejected location
+const SUGGESTION_RING_STEP_M: u32 = 100; // [m] radius increment between rings
+const SUGGESTION_RINGS: u32 = 100;
+const SUGGESTION_BEARINGS: u32 = 16; // candidates per ring
+const METERS_PER_DEGREE: u32 = 111_195; // [m/°] along a meridian
 
-const DATELINE_DISTANCE_M: u32 = 1_000_000; // meetups may not be closer to dateline (or poles) than this
+pub const MAX_VENUE_NAME_LEN: usize = 64; // [bytes]
+pub const MAX_LOCATION_DESCRIPTION_LEN: usize = 256; // [bytes]
+
+/// maximum number of vertices of a currency's region polygon
+pub const MAX_REGION_VERTICES: usize = 64;
+// max number of distinct locks per account and currency
+pub const MAX_LOCKS: usize = 16;
 
 const NORTH_POLE: Location = Location {
     lon: Degree::from_bits(0i64),
@@ -78,7 +600,34 @@ const SOUTH_POLE: Location = Location {
     lon: Degree::from_bits(0i64),
     lat: Degree::from_bits(-90i64 << 32),
 };
+
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+/// longer geohashes describe cells below canonical precision
+pub const MAX_GEOHASH_PRECISION: usize = 12;
+
+// the 9 least significant fractional bits of a canonical Degree are zero
+const NON_CANONICAL_BITS_MASK: i64 = (1 << 9) - 1;
+
 const DATELINE_LON: Degree = Degree::from_bits(180i64 << 32);
+const FULL_TURN: Degree = Degree::from_bits(360i64 << 32);
+// the ceremony date changes here. Approximates the international date line in the south
+// pacific, between Samoa and American Samoa, so Fiji, Tonga and Samoa meet on the same date
+const CEREMONY_DATE_LINE_LON: Degree = Degree::from_bits(-171i64 << 32);
+// haversine_distance switches to equirectangular_distance below 1/8° (~14km) in both directions
+const EQUIRECTANGULAR_MAX_DEGREES: Degree = Degree::from_bits(1i64 << 29);
+
+// dec2hex(round(ln(2) * 2^64),16)
+const LN_2: I64F64 = I64F64::from_bits(0xB17217F7D1CF79AC);
+
+// exp(-45) is below the resolution of BalanceType
+const MAX_DEMURRAGE_EXPONENT: i32 = 45;
+// balances of new currencies halve every this many blocks (~1 year at 5s blocks)
+pub const DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS: u64 = 6_151_680;
+pub const DEFAULT_NOMINAL_INCOME: BalanceType = I64F64::from_bits(1 << 64); // 1 unit per ceremony
+const EXCHANGE_RESERVE_ID: ModuleId = ModuleId(*b"ecc/xres"); // settles conversions
+const MS_PER_DAY: u64 = 86_400_000;
+const DAYS_PER_YEAR: u32 = 365;
+const NEG_LN_ITERATIONS: u32 = 32;
 
 // dec2hex(round(pi/180 * 2^64),16)
 const RADIANS_PER_DEGREE: U0F64 = U0F64::from_bits(0x0477D1A894A74E40);
@@ -93,6 +642,18 @@ decl_storage! {
         Bootstrappers get(fn bootstrappers): map hasher(blake2_128_concat) CurrencyIdentifier => Vec<T::AccountId>;
         CurrencyIdentifiers get(fn currency_identifiers): Vec<CurrencyIdentifier>;
         CurrencyProperties get(fn currency_properties): map hasher(blake2_128_concat) CurrencyIdentifier => CurrencyPropertiesType;
+        // simple polygon which contains all locations of a currency. empty if not specified
+        Regions get(fn regions): map hasher(blake2_128_concat) CurrencyIdentifier => Vec<Location>;
+        LocationMetadata get(fn location_metadata): double_map hasher(blake2_128_concat) CurrencyIdentifier, hasher(twox_64_concat) LocationIndexType => Option<LocationMetadataType>;
+        Balance get(fn balance_entry): double_map hasher(blake2_128_concat) CurrencyIdentifier, hasher(blake2_128_concat) T::AccountId => BalanceEntry<T::BlockNumber>;
+        Reserved get(fn reserved_entry): double_map hasher(blake2_128_concat) CurrencyIdentifier, hasher(blake2_128_concat) T::AccountId => BalanceEntry<T::BlockNumber>;
+        Locks get(fn locks): double_map hasher(blake2_128_concat) CurrencyIdentifier, hasher(blake2_128_concat) T::AccountId => Vec<BalanceLock<T::BlockNumber>>;
+        // sum of all balances of a currency, decaying like them
+        TotalIssuance get(fn total_issuance_entry): map hasher(blake2_128_concat) CurrencyIdentifier => BalanceEntry<T::BlockNumber>;
+        // amount of a currency charged per native fee unit. Fees can't be paid in currencies without a rate
+        FeeConversionRate get(fn fee_conversion_rate): map hasher(blake2_128_concat) CurrencyIdentifier => Option<BalanceType>;
+        // amount of the second currency one unit of the first one is worth
+        ExchangeRate get(fn exchange_rate): double_map hasher(blake2_128_concat) CurrencyIdentifier, hasher(blake2_128_concat) CurrencyIdentifier => Option<BalanceType>;
         // TODO: replace this with on-chain governance
         CurrencyMaster get(fn currency_master) config(): T::AccountId;
     }
@@ -100,6 +661,10 @@ decl_storage! {
 
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        const MinLocationDistanceM: u32 = T::MinLocationDistanceM::get();
+        const DefaultLocationCapacity: u32 = T::DefaultLocationCapacity::get();
+        const ExpectedBlockTimeMs: u64 = T::ExpectedBlockTimeMs::get();
+
         fn deposit_event() = default;
         // FIXME: this function has complexity O(n^2)!
         // where n is the number of all locations of all currencies
@@ -109,39 +674,15 @@ decl_module! {
             let sender = ensure_signed(origin)?;
             let cid = CurrencyIdentifier::from(blake2_256(&(loc.clone(), bootstrappers.clone()).encode()));
             let cids = Self::currency_identifiers();
-            ensure!(!cids.contains(&cid), "currency already registered");
-
-            for l1 in loc.iter() {
-                ensure!(Self::is_valid_geolocation(&l1), "invalid geolocation specified");
-                //test within this currencies' set
-                for l2 in loc.iter() {
-                    if l2 == l1 { continue }
-                    ensure!(Self::solar_trip_time(&l1, &l2) >= MIN_SOLAR_TRIP_TIME_S, "minimum solar trip time violated within supplied locations");
-                }
-                // prohibit proximity to poles
-                if Self::haversine_distance(&l1, &NORTH_POLE) < DATELINE_DISTANCE_M
-                    || Self::haversine_distance(&l1, &SOUTH_POLE) < DATELINE_DISTANCE_M {
-                    print_utf8(b"location distance violation for:");
-                    print_hex(&l1.encode());
-                    return Err(<Error<T>>::MinimumDistanceViolationToPole.into());
-                }
-                // prohibit proximity to dateline
-                let dateline_proxy = Location { lat: l1.lat, lon: DATELINE_LON };
-                if Self::haversine_distance(&l1, &dateline_proxy) < DATELINE_DISTANCE_M {
-                    print_utf8(b"location distance violation for:");
-                    print_hex(&l1.encode());
-                    return Err(<Error<T>>::MinimumDistanceViolationToDateLine.into());
-                }
-                // test against all other currencies globally
-                for other in cids.iter() {
-                    for l2 in Self::locations(other) {
-                        if Self::solar_trip_time(&l1, &l2) < MIN_SOLAR_TRIP_TIME_S {
-                            print_utf8(b"location distance violation for:");
-                            print_hex(&other.encode());
-                            return Err(<Error<T>>::MinimumDistanceViolationToOtherCurrency.into());
-                        }
-                    }
+            ensure!(!cids.contains(&cid), <Error<T>>::CurrencyAlreadyRegistered);
+
+            let issues = Self::validate_locations(&loc);
+            if let Some(issue) = issues.first() {
+                // failed calls keep their events, so clients learn about every issue
+                for i in issues.iter() {
+                    Self::deposit_event(RawEvent::LocationRejected(i.reason.cid_conflict(), i.location_index, i.reason));
                 }
+                return Err(Self::rejection_error(&issue.reason).into());
             }
 
             <CurrencyIdentifiers>::mutate(|v| v.push(cid));
@@ -150,12 +691,130 @@ decl_module! {
             <CurrencyProperties>::insert(&cid, 
                 CurrencyPropertiesType {
                     name_utf8: b"encointer dummy".to_vec(), 
-                    demurrage_per_block: Demurrage::from_bits(0x0000000000000000000001E3F0A8A973_i128)
+                    demurrage_per_block: demurrage_from_half_life(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS)
+                        .unwrap_or_default(),
+                    nominal_income: DEFAULT_NOMINAL_INCOME,
                 }
             );
             Self::deposit_event(RawEvent::CurrencyRegistered(sender, cid));
-            print_utf8(b"registered currency wth cid:");
-            print_hex(&cid.encode());
+            Ok(())
+        }
+
+        /// define the area a currency serves as a simple polygon in lat/lon.
+        /// Regions are planar in lat/lon, so they can't span the antimeridian.
+        // FIXME: this function has complexity O(n*m) where n is the number of currencies and m
+        // the number of region vertices
+        #[weight = 10_000]
+        pub fn set_region(origin, cid: CurrencyIdentifier, region: Vec<Location>) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_bootstrapper(&sender, &cid)?;
+            let cids = Self::currency_identifiers();
+            ensure!(region.len() >= 3 && region.len() <= MAX_REGION_VERTICES, <Error<T>>::InvalidRegion);
+            ensure!(region.iter().all(Self::is_valid_geolocation), <Error<T>>::InvalidRegion);
+            ensure!(Self::is_simple_polygon(&region), <Error<T>>::InvalidRegion);
+            ensure!(Self::locations(&cid).iter().all(|l| Self::is_inside_region(l, &region)),
+                <Error<T>>::LocationOutsideRegion);
+            for other in cids.iter().filter(|other| **other != cid) {
+                ensure!(!Self::regions_overlap(&region, &Self::regions(other)), <Error<T>>::RegionOverlap);
+                // new_currency rejects locations inside foreign regions, so regions can't claim them either
+                ensure!(!Self::locations(other).iter().any(|l| Self::is_inside_region(l, &region)),
+                    <Error<T>>::RegionContainsForeignLocation);
+            }
+            <Regions>::insert(&cid, &region);
+            Self::deposit_event(RawEvent::RegionSet(cid));
+            Ok(())
+        }
+
+        /// describe the venue of the location at `location_index` of a currency
+        #[weight = 10_000]
+        pub fn set_location_metadata(origin, cid: CurrencyIdentifier, location_index: LocationIndexType, metadata: LocationMetadataType) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_bootstrapper(&sender, &cid)?;
+            ensure!((location_index as usize) < Self::locations(&cid).len(), <Error<T>>::LocationIndexOutOfBounds);
+            ensure!(metadata.venue_name_utf8.len() <= MAX_VENUE_NAME_LEN, <Error<T>>::MetadataTooLong);
+            ensure!(metadata.description_utf8.len() <= MAX_LOCATION_DESCRIPTION_LEN, <Error<T>>::MetadataTooLong);
+            <LocationMetadata>::insert(&cid, location_index, &metadata);
+            Self::deposit_event(RawEvent::LocationMetadataUpdated(cid, location_index));
+            Ok(())
+        }
+
+        /// set the number of participants the location at `location_index` can host.
+        /// 0 resets to `DefaultLocationCapacity`
+        #[weight = 10_000]
+        pub fn set_location_capacity(origin, cid: CurrencyIdentifier, location_index: LocationIndexType, capacity: u32) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_bootstrapper(&sender, &cid)?;
+            ensure!((location_index as usize) < Self::locations(&cid).len(), <Error<T>>::LocationIndexOutOfBounds);
+            <LocationMetadata>::mutate(&cid, location_index, |metadata| {
+                metadata.get_or_insert_with(Default::default).max_participants = capacity
+            });
+            Self::deposit_event(RawEvent::LocationMetadataUpdated(cid, location_index));
+            Ok(())
+        }
+
+        /// transfer `amount` of currency `cid` to `to`. Demurrage is applied to both balances first
+        #[weight = 10_000]
+        pub fn transfer(origin, cid: CurrencyIdentifier, to: T::AccountId, amount: BalanceType) -> DispatchResult {
+            let from = ensure_signed(origin)?;
+            ensure!(Self::currency_identifiers().contains(&cid), <Error<T>>::CurrencyNotRegistered);
+            Self::do_transfer(&cid, &from, &to, amount)?;
+            Self::deposit_event(RawEvent::Transferred(cid, from, to, amount));
+            Ok(())
+        }
+
+        /// set the amount each participant of a ceremony of `cid` receives
+        #[weight = 10_000]
+        pub fn set_nominal_income(origin, cid: CurrencyIdentifier, income: BalanceType) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_bootstrapper(&sender, &cid)?;
+            ensure!(income >= BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+            <CurrencyProperties>::mutate(&cid, |p| p.nominal_income = income);
+            Self::deposit_event(RawEvent::NominalIncomeSet(cid, income));
+            Ok(())
+        }
+
+        /// pay `amount` of `from_cid` to `to`, who receives its value in `to_cid` at the stored
+        /// exchange rate. Fails if `to` would receive less than `min_received`.
+        /// Between different currencies, the exchange reserve settles the conversion,
+        /// see `do_transfer_converted`
+        #[weight = 10_000]
+        pub fn transfer_converted(origin, from_cid: CurrencyIdentifier, to_cid: CurrencyIdentifier, to: T::AccountId, amount: BalanceType, min_received: BalanceType) -> DispatchResult {
+            let from = ensure_signed(origin)?;
+            let received = Self::do_transfer_converted(&from_cid, &to_cid, &from, &to, amount, min_received)?;
+            Self::deposit_event(RawEvent::TransferredConverted(from_cid, to_cid, from, to, amount, received));
+            Ok(())
+        }
+
+        /// set the amount of `cid` charged per native fee unit when paying fees in `cid`
+        #[weight = 10_000]
+        pub fn set_fee_conversion_rate(origin, cid: CurrencyIdentifier, rate: BalanceType) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            ensure!(sender == Self::currency_master(), <Error<T>>::NotCurrencyMaster);
+            ensure!(Self::currency_identifiers().contains(&cid), <Error<T>>::CurrencyNotRegistered);
+            ensure!(rate > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+            <FeeConversionRate>::insert(&cid, rate);
+            Self::deposit_event(RawEvent::FeeConversionRateSet(cid, rate));
+            Ok(())
+        }
+
+        /// set the amount of `to` one unit of `from` is worth. Converting back and forth at this
+        /// rate and the one from `to` to `from` may not gain value
+        #[weight = 10_000]
+        pub fn set_exchange_rate(origin, from: CurrencyIdentifier, to: CurrencyIdentifier, rate: BalanceType) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            ensure!(sender == Self::currency_master(), <Error<T>>::NotCurrencyMaster);
+            let cids = Self::currency_identifiers();
+            ensure!(cids.contains(&from) && cids.contains(&to), <Error<T>>::CurrencyNotRegistered);
+            ensure!(from != to, <Error<T>>::InvalidCurrencyPair);
+            ensure!(rate > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+            if let Some(inverse) = Self::exchange_rate(&to, &from) {
+                ensure!(
+                    rate.checked_mul(inverse).map_or(false, |r| r <= BalanceType::from_num(1)),
+                    <Error<T>>::InconsistentExchangeRate
+                );
+            }
+            <ExchangeRate>::insert(&from, &to, rate);
+            Self::deposit_event(RawEvent::ExchangeRateSet(from, to, rate));
             Ok(())
         }
     }
@@ -167,79 +826,1060 @@ decl_event!(
         AccountId = <T as system::Trait>::AccountId,
     {
         CurrencyRegistered(AccountId, CurrencyIdentifier),
+        /// conflicting currency, index and reason of a location rejected by `new_currency`
+        LocationRejected(Option<CurrencyIdentifier>, LocationIndexType, LocationRejectionReason),
+        RegionSet(CurrencyIdentifier),
+        LocationMetadataUpdated(CurrencyIdentifier, LocationIndexType),
+        Transferred(CurrencyIdentifier, AccountId, AccountId, BalanceType),
+        NominalIncomeSet(CurrencyIdentifier, BalanceType),
+        Issued(CurrencyIdentifier, AccountId, BalanceType),
+        /// amount removed from an account and the total issuance of a currency
+        Burned(CurrencyIdentifier, AccountId, BalanceType),
+        FeeConversionRateSet(CurrencyIdentifier, BalanceType),
+        ExchangeRateSet(CurrencyIdentifier, CurrencyIdentifier, BalanceType),
+        /// sent amount in the first currency, received amount in the second one
+        TransferredConverted(CurrencyIdentifier, CurrencyIdentifier, AccountId, AccountId, BalanceType, BalanceType),
     }
 );
 
 decl_error! {
 	pub enum Error for Module<T: Trait> {
+		/// a currency with the same locations and bootstrappers already exists
+		CurrencyAlreadyRegistered,
+		/// coordinates out of range or not canonical
+		InvalidGeolocation,
+		/// minimum solar trip time violated between locations of the same currency
+		MinimumSolarTripTimeViolatedWithinCurrency,
 		/// minimum distance violated towards pole
         MinimumDistanceViolationToPole,
-        /// minimum distance violated towards dateline
-        MinimumDistanceViolationToDateLine,
         /// minimum distance violated towards other currency's location
 		MinimumDistanceViolationToOtherCurrency,
+		/// fixed-point computation of distances or trip times failed
+		GeoComputationFailed,
+		/// two locations are closer than `MinLocationDistanceM`
+		MinimumLocationDistanceViolated,
+		/// location lies within the region of another currency
+		LocationInsideForeignRegion,
+		/// no currency registered with this identifier
+		CurrencyNotRegistered,
+		/// sender is not a bootstrapper of this currency
+		NotBootstrapper,
+		/// region is not a simple polygon of valid locations with a bounded number of vertices
+		InvalidRegion,
+		/// a location of the currency lies outside of the region
+		LocationOutsideRegion,
+		/// region overlaps the region of another currency
+		RegionOverlap,
+		/// currency has no location with this index
+		LocationIndexOutOfBounds,
+		/// venue name or description exceed their maximum length
+		MetadataTooLong,
+		/// amount must be positive
+		InvalidAmount,
+		/// balance too low to send amount
+		BalanceTooLow,
+		/// balance would exceed the range of `BalanceType`
+		BalanceOverflow,
+		/// sender is not the currency master
+		NotCurrencyMaster,
+		/// a currency can't be exchanged for itself
+		InvalidCurrencyPair,
+		/// no exchange rate set between these currencies
+		NoExchangeRate,
+		/// recipient would receive less than the specified minimum
+		SlippageExceeded,
+		/// amount is locked
+		BalanceLocked,
+		/// region contains a location of another currency
+		RegionContainsForeignLocation,
+		/// product of an exchange rate and the reverse one exceeds 1
+		InconsistentExchangeRate,
+		/// exchange reserve can't pay the converted amount
+		InsufficientExchangeReserve,
+		/// account already has `MAX_LOCKS` locks in this currency
+		TooManyLocks,
 	}
 }
 
+impl<T: Trait> From<GeoError> for Error<T> {
+    fn from(_: GeoError) -> Self {
+        <Error<T>>::GeoComputationFailed
+    }
+}
+
 impl<T: Trait> Module<T> {
-    fn solar_trip_time(from: &Location, to: &Location) -> i32 {
-        // FIXME: replace by fixpoint implementation within runtime.
-        let d = Module::<T>::haversine_distance(&from, &to) as i32;
-        // FIXME: this will not panic, but make sure!
-        let dt = from
-            .lon
-            .checked_sub(to.lon)
-            .unwrap()
-            .checked_div(Degree::from_num(1))
-            .unwrap()
-            .checked_mul(Degree::from_num(240))
-            .unwrap(); // 24h * 3600s / 360° = 240s/°
-        let tflight = d.checked_div(MAX_SPEED_MPS).unwrap();
-        let dt: i32 = dt.abs().lossy_into();
-        tflight - dt
+    /// Checks a proposed set of locations for a new currency without registering it.
+    /// Returns all issues found, ordered by location index. An empty result means that
+    /// `new_currency` will accept these locations.
+    pub fn validate_locations(loc: &[Location]) -> Vec<LocationValidationIssue> {
+        let cids = Self::currency_identifiers();
+        let min_distance = T::MinLocationDistanceM::get();
+        let mut issues = Vec::new();
+        for (i, l1) in loc.iter().enumerate() {
+            let mut reject = |reason: LocationRejectionReason| {
+                issues.push(LocationValidationIssue {
+                    location_index: i as LocationIndexType,
+                    reason,
+                })
+            };
+            if !Self::is_valid_geolocation(l1) {
+                reject(LocationRejectionReason::InvalidCoordinate);
+                continue;
+            }
+            //test within this currencies' set
+            for (j, l2) in loc.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let j = j as LocationIndexType;
+                match (Self::solar_trip_time(l1, l2), Self::haversine_distance(l1, l2)) {
+                    (Ok(t), Ok(d)) => {
+                        if t < MIN_SOLAR_TRIP_TIME_S {
+                            reject(LocationRejectionReason::TooCloseToSibling(j));
+                        }
+                        if d < min_distance {
+                            reject(LocationRejectionReason::BelowMinimumDistanceToSibling(j));
+                        }
+                    }
+                    _ => reject(LocationRejectionReason::GeoComputationFailed),
+                }
+            }
+            // prohibit proximity to poles
+            match (
+                Self::haversine_distance(l1, &NORTH_POLE),
+                Self::haversine_distance(l1, &SOUTH_POLE),
+            ) {
+                (Ok(dn), Ok(ds)) if dn.min(ds) >= POLE_DISTANCE_M => (),
+                (Ok(_), Ok(_)) => reject(LocationRejectionReason::NearPole),
+                _ => reject(LocationRejectionReason::GeoComputationFailed),
+            }
+            // test against all other currencies globally
+            for other in cids.iter() {
+                if Self::is_inside_region(l1, &Self::regions(other)) {
+                    reject(LocationRejectionReason::InsideForeignRegion(*other));
+                }
+                for (j, l2) in Self::locations(other).iter().enumerate() {
+                    let j = j as LocationIndexType;
+                    match (Self::solar_trip_time(l1, l2), Self::haversine_distance(l1, l2)) {
+                        (Ok(t), Ok(d)) => {
+                            if t < MIN_SOLAR_TRIP_TIME_S {
+                                reject(LocationRejectionReason::TooCloseToCurrency(*other, j));
+                            }
+                            if d < min_distance {
+                                reject(LocationRejectionReason::BelowMinimumDistanceToCurrency(
+                                    *other, j,
+                                ));
+                            }
+                        }
+                        _ => reject(LocationRejectionReason::GeoComputationFailed),
+                    }
+                }
+            }
+        }
+        issues
+    }
+
+    /// Pairwise distances and solar trip times of all locations of `cid`, plus the foreign
+    /// location with the smallest solar trip time for each of them, so bootstrappers can
+    /// audit their margins against `MIN_SOLAR_TRIP_TIME_S` and `MinLocationDistanceM`.
+    /// Intended to be run off-chain.
+    pub fn location_report(cid: &CurrencyIdentifier) -> Result<LocationReport, GeoError> {
+        let loc = Self::locations(cid);
+        let mut report = LocationReport {
+            min_solar_trip_time_s: MIN_SOLAR_TRIP_TIME_S,
+            min_location_distance_m: T::MinLocationDistanceM::get(),
+            ..Default::default()
+        };
+        for l1 in loc.iter() {
+            let mut distances = Vec::with_capacity(loc.len());
+            let mut trip_times = Vec::with_capacity(loc.len());
+            for l2 in loc.iter() {
+                distances.push(Self::haversine_distance(l1, l2)?);
+                trip_times.push(Self::solar_trip_time(l1, l2)?);
+            }
+            report.distances_m.push(distances);
+            report.solar_trip_times_s.push(trip_times);
+
+            let mut closest: Option<ForeignLocationProximity> = None;
+            for other in Self::currency_identifiers().iter().filter(|other| *other != cid) {
+                for (j, l2) in Self::locations(other).iter().enumerate() {
+                    let solar_trip_time_s = Self::solar_trip_time(l1, l2)?;
+                    if closest.map_or(true, |c| solar_trip_time_s < c.solar_trip_time_s) {
+                        closest = Some(ForeignLocationProximity {
+                            cid: *other,
+                            location_index: j as LocationIndexType,
+                            distance_m: Self::haversine_distance(l1, l2)?,
+                            solar_trip_time_s,
+                        });
+                    }
+                }
+            }
+            report.closest_foreign.push(closest);
+        }
+        Ok(report)
+    }
+
+    /// Searches outward from `loc` for the closest location that would be accepted as a
+    /// single new location by `new_currency`. Candidates lie on rings spaced by
+    /// `SUGGESTION_RING_STEP_M` up to a radius of 10km. Returns `None` if no candidate is valid,
+    /// e.g. within the pole exclusion zone or the region of another currency.
+    /// Intended to be run off-chain as this validates up to 1600 candidates.
+    pub fn suggest_nearest_valid_location(loc: &Location) -> Option<Location> {
+        type I = I32F32;
+        let origin = loc.canonicalize();
+        if origin.validate().is_err() {
+            return None;
+        }
+        if Self::validate_locations(&[origin]).is_empty() {
+            return Some(origin);
+        }
+        let rad = I::lossy_from(RADIANS_PER_DEGREE);
+        let cos_lat = cos(I::from(origin.lat) * rad);
+        if cos_lat <= I::from_num(0) {
+            return None;
+        }
+        for ring in 1..=SUGGESTION_RINGS {
+            let radius =
+                I::from_num(ring * SUGGESTION_RING_STEP_M) / I::from_num(METERS_PER_DEGREE);
+            for b in 0..SUGGESTION_BEARINGS {
+                let bearing = I::from_num(360 * b / SUGGESTION_BEARINGS) * rad;
+                let lat = origin.lat + radius * cos(bearing);
+                // wrap around the antimeridian. Close to the poles, the step may overflow
+                let lon = match (radius * sin(bearing))
+                    .checked_div(cos_lat)
+                    .and_then(|dlon| origin.lon.checked_add(dlon))
+                    .and_then(|lon| Self::lon_difference(lon, I::from_num(0)).ok())
+                {
+                    Some(lon) => lon,
+                    None => continue,
+                };
+                let candidate = Location { lat, lon }.canonicalize();
+                if candidate.validate().is_ok()
+                    && Self::validate_locations(&[candidate]).is_empty()
+                {
+                    return Some(candidate);
+                }
+            }
+        }
+        None
+    }
+
+    /// per-block demurrage of `cid`, 0 if no such currency is registered
+    pub fn demurrage_per_block(cid: &CurrencyIdentifier) -> Demurrage {
+        Self::currency_properties(cid).demurrage_per_block
+    }
+
+    /// per-block demurrage for a half-life of `days` at the expected block time
+    pub fn demurrage_from_half_life_days(days: u32) -> Option<Demurrage> {
+        crate::demurrage_from_half_life(blocks_per_days(days, T::ExpectedBlockTimeMs::get())?)
+    }
+
+    /// per-block demurrage for an annual loss of `rate` at the expected block time
+    pub fn demurrage_from_annual_rate(rate: Percent) -> Option<Demurrage> {
+        crate::demurrage_from_annual_rate(rate, T::ExpectedBlockTimeMs::get())
+    }
+
+    /// balance of `who` in `cid` with demurrage applied up to the current block
+    pub fn balance(cid: &CurrencyIdentifier, who: &T::AccountId) -> BalanceType {
+        Self::current_balance_entry(cid, who).principal
+    }
+
+    /// balance entry of `who` in `cid` with demurrage applied up to the current block
+    fn current_balance_entry(
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+    ) -> BalanceEntry<T::BlockNumber> {
+        Self::apply_demurrage_to_entry(cid, Self::balance_entry(cid, who))
+    }
+
+    /// money supply of `cid` with demurrage applied up to the current block
+    pub fn total_issuance(cid: &CurrencyIdentifier) -> BalanceType {
+        Self::current_total_issuance_entry(cid).principal
+    }
+
+    fn current_total_issuance_entry(cid: &CurrencyIdentifier) -> BalanceEntry<T::BlockNumber> {
+        Self::apply_demurrage_to_entry(cid, Self::total_issuance_entry(cid))
+    }
+
+    fn apply_demurrage_to_entry(
+        cid: &CurrencyIdentifier,
+        entry: BalanceEntry<T::BlockNumber>,
+    ) -> BalanceEntry<T::BlockNumber> {
+        let now = <system::Module<T>>::block_number();
+        if entry.last_update >= now {
+            return entry;
+        }
+        let elapsed: u32 = (now - entry.last_update).unique_saturated_into();
+        BalanceEntry {
+            principal: apply_demurrage(
+                entry.principal,
+                Self::currency_properties(cid).demurrage_per_block,
+                elapsed,
+            ),
+            last_update: now,
+        }
+    }
+
+    fn do_transfer(
+        cid: &CurrencyIdentifier,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: BalanceType,
+    ) -> Result<(), Error<T>> {
+        ensure!(amount > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+        let mut from_entry = Self::current_balance_entry(cid, from);
+        ensure!(from_entry.principal >= amount, <Error<T>>::BalanceTooLow);
+        Self::ensure_can_withdraw(cid, from, from_entry.principal - amount)?;
+        if from == to {
+            return Ok(());
+        }
+        let mut to_entry = Self::current_balance_entry(cid, to);
+        to_entry.principal = to_entry
+            .principal
+            .checked_add(amount)
+            .ok_or(<Error<T>>::BalanceOverflow)?;
+        from_entry.principal -= amount;
+        <Balance<T>>::insert(cid, from, from_entry);
+        <Balance<T>>::insert(cid, to, to_entry);
+        Ok(())
+    }
+
+    fn do_issue(
+        cid: &CurrencyIdentifier,
+        beneficiaries: &[T::AccountId],
+        amount: BalanceType,
+    ) -> Result<(), Error<T>> {
+        ensure!(Self::currency_identifiers().contains(cid), <Error<T>>::CurrencyNotRegistered);
+        ensure!(amount > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+        let count = BalanceType::checked_from_num(beneficiaries.len())
+            .ok_or(<Error<T>>::BalanceOverflow)?;
+        let mut issuance = Self::current_total_issuance_entry(cid);
+        issuance.principal = amount
+            .checked_mul(count)
+            .and_then(|issued| issuance.principal.checked_add(issued))
+            .ok_or(<Error<T>>::BalanceOverflow)?;
+        // credit a local copy first, so nothing is written if any balance overflows
+        let mut credited: BTreeMap<T::AccountId, BalanceEntry<T::BlockNumber>> = BTreeMap::new();
+        for who in beneficiaries {
+            let entry = credited
+                .entry(who.clone())
+                .or_insert_with(|| Self::current_balance_entry(cid, who));
+            entry.principal = entry
+                .principal
+                .checked_add(amount)
+                .ok_or(<Error<T>>::BalanceOverflow)?;
+        }
+        for (who, entry) in credited {
+            <Balance<T>>::insert(cid, &who, entry);
+        }
+        <TotalIssuance<T>>::insert(cid, issuance);
+        Ok(())
+    }
+
+    /// account which settles conversions between currencies. Anyone may fund it by transferring
+    /// to it
+    pub fn exchange_reserve_account() -> T::AccountId {
+        EXCHANGE_RESERVE_ID.into_account()
+    }
+
+    /// returns the amount received in `to_cid`.
+    ///
+    /// Conversions between different currencies are settled against the exchange reserve:
+    /// `amount` of `from_cid` goes to `exchange_reserve_account`, which pays the converted
+    /// amount of `to_cid` out of its own balance. Conversions therefore never change the money
+    /// supply of a currency and are limited by the funds of the reserve.
+    fn do_transfer_converted(
+        from_cid: &CurrencyIdentifier,
+        to_cid: &CurrencyIdentifier,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: BalanceType,
+        min_received: BalanceType,
+    ) -> Result<BalanceType, Error<T>> {
+        let cids = Self::currency_identifiers();
+        ensure!(
+            cids.contains(from_cid) && cids.contains(to_cid),
+            <Error<T>>::CurrencyNotRegistered
+        );
+        ensure!(amount > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+        ensure!(
+            from_cid == to_cid || Self::exchange_rate(from_cid, to_cid).is_some(),
+            <Error<T>>::NoExchangeRate
+        );
+        let received =
+            Self::convert(amount, from_cid, to_cid).ok_or(<Error<T>>::BalanceOverflow)?;
+        ensure!(received >= min_received, <Error<T>>::SlippageExceeded);
+        if from_cid == to_cid {
+            Self::do_transfer(from_cid, from, to, amount)?;
+            return Ok(received);
+        }
+        ensure!(received > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+
+        // check both legs before moving any funds, so the conversion is atomic
+        let reserve = Self::exchange_reserve_account();
+        Self::balance_entry_after_withdrawal(from_cid, from, amount)?;
+        Self::balance_entry_after_withdrawal(to_cid, &reserve, received)
+            .map_err(|_| <Error<T>>::InsufficientExchangeReserve)?;
+        ensure!(
+            Self::balance(from_cid, &reserve).checked_add(amount).is_some()
+                && Self::balance(to_cid, to).checked_add(received).is_some(),
+            <Error<T>>::BalanceOverflow
+        );
+        Self::do_transfer(from_cid, from, &reserve, amount)?;
+        Self::do_transfer(to_cid, &reserve, to, received)?;
+        Ok(received)
+    }
+
+    /// reserved balance of `who` in `cid` with demurrage applied up to the current block
+    pub fn reserved_balance(cid: &CurrencyIdentifier, who: &T::AccountId) -> BalanceType {
+        Self::apply_demurrage_to_entry(cid, Self::reserved_entry(cid, who)).principal
+    }
+
+    /// amount of the balance of `who` in `cid` which is locked, i.e. the largest lock
+    pub fn frozen_balance(cid: &CurrencyIdentifier, who: &T::AccountId) -> BalanceType {
+        Self::locks(cid, who)
+            .into_iter()
+            .map(|lock| Self::apply_demurrage_to_entry(cid, lock.amount).principal)
+            .fold(BalanceType::from_num(0), |frozen, amount| frozen.max(amount))
+    }
+
+    fn ensure_can_withdraw(
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+        remaining: BalanceType,
+    ) -> Result<(), Error<T>> {
+        ensure!(remaining >= Self::frozen_balance(cid, who), <Error<T>>::BalanceLocked);
+        Ok(())
+    }
+
+    fn do_reserve(
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+        amount: BalanceType,
+    ) -> Result<(), Error<T>> {
+        ensure!(Self::currency_identifiers().contains(cid), <Error<T>>::CurrencyNotRegistered);
+        ensure!(amount > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+        let mut entry = Self::current_balance_entry(cid, who);
+        ensure!(entry.principal >= amount, <Error<T>>::BalanceTooLow);
+        entry.principal -= amount;
+        Self::ensure_can_withdraw(cid, who, entry.principal)?;
+        let mut reserved = Self::apply_demurrage_to_entry(cid, Self::reserved_entry(cid, who));
+        reserved.principal = reserved
+            .principal
+            .checked_add(amount)
+            .ok_or(<Error<T>>::BalanceOverflow)?;
+        <Balance<T>>::insert(cid, who, entry);
+        <Reserved<T>>::insert(cid, who, reserved);
+        Ok(())
+    }
+
+    /// returns the amount which could not be unreserved
+    fn do_unreserve(
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+        amount: BalanceType,
+    ) -> BalanceType {
+        let zero = BalanceType::from_num(0);
+        if amount <= zero {
+            return zero;
+        }
+        let mut reserved = Self::apply_demurrage_to_entry(cid, Self::reserved_entry(cid, who));
+        let actual = amount.min(reserved.principal);
+        reserved.principal -= actual;
+        let mut entry = Self::current_balance_entry(cid, who);
+        entry.principal = entry.principal.saturating_add(actual);
+        <Balance<T>>::insert(cid, who, entry);
+        <Reserved<T>>::insert(cid, who, reserved);
+        amount - actual
+    }
+
+    fn do_set_lock(
+        lock_id: LockIdentifier,
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+        amount: BalanceType,
+    ) -> Result<(), Error<T>> {
+        ensure!(Self::currency_identifiers().contains(cid), <Error<T>>::CurrencyNotRegistered);
+        ensure!(amount > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+        let lock = BalanceLock {
+            id: lock_id,
+            amount: BalanceEntry {
+                principal: amount,
+                last_update: <system::Module<T>>::block_number(),
+            },
+        };
+        let mut locks = Self::locks(cid, who);
+        match locks.iter().position(|l| l.id == lock_id) {
+            Some(i) => locks[i] = lock,
+            None => {
+                ensure!(locks.len() < MAX_LOCKS, <Error<T>>::TooManyLocks);
+                locks.push(lock)
+            }
+        }
+        <Locks<T>>::insert(cid, who, locks);
+        Ok(())
+    }
+
+    fn do_remove_lock(lock_id: LockIdentifier, cid: &CurrencyIdentifier, who: &T::AccountId) {
+        let mut locks = Self::locks(cid, who);
+        locks.retain(|l| l.id != lock_id);
+        if locks.is_empty() {
+            <Locks<T>>::remove(cid, who);
+        } else {
+            <Locks<T>>::insert(cid, who, locks);
+        }
+    }
+
+    /// balance entry of `who` after withdrawing `amount`, if the withdrawal is possible
+    fn balance_entry_after_withdrawal(
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+        amount: BalanceType,
+    ) -> Result<BalanceEntry<T::BlockNumber>, Error<T>> {
+        ensure!(Self::currency_identifiers().contains(cid), <Error<T>>::CurrencyNotRegistered);
+        ensure!(amount > BalanceType::from_num(0), <Error<T>>::InvalidAmount);
+        let mut entry = Self::current_balance_entry(cid, who);
+        ensure!(entry.principal >= amount, <Error<T>>::BalanceTooLow);
+        entry.principal -= amount;
+        Self::ensure_can_withdraw(cid, who, entry.principal)?;
+        Ok(entry)
+    }
+
+    /// remove `amount` of `cid` from the balance of `who` and from the total issuance
+    fn do_withdraw(
+        cid: &CurrencyIdentifier,
+        who: &T::AccountId,
+        amount: BalanceType,
+    ) -> Result<(), Error<T>> {
+        let entry = Self::balance_entry_after_withdrawal(cid, who, amount)?;
+        let mut issuance = Self::current_total_issuance_entry(cid);
+        // rounding of demurrage may leave the issuance marginally below the sum of balances
+        issuance.principal = (issuance.principal - amount).max(BalanceType::from_num(0));
+        <Balance<T>>::insert(cid, who, entry);
+        <TotalIssuance<T>>::insert(cid, issuance);
+        Ok(())
+    }
+
+    /// value of `amount` of `from` in `to` at the stored exchange rate.
+    /// `None` if there is no rate or the result overflows
+    pub fn convert(
+        amount: BalanceType,
+        from: &CurrencyIdentifier,
+        to: &CurrencyIdentifier,
+    ) -> Option<BalanceType> {
+        if from == to {
+            return Some(amount);
+        }
+        amount.checked_mul(Self::exchange_rate(from, to)?)
+    }
+
+    /// number of participants the location at `location_index` of `cid` can host.
+    /// 0 if there is no such location
+    pub fn location_capacity(cid: &CurrencyIdentifier, location_index: LocationIndexType) -> u32 {
+        if location_index as usize >= Self::locations(cid).len() {
+            return 0;
+        }
+        Self::capacity_of_existing(cid, location_index)
+    }
+
+    /// number of participants all locations of `cid` can host together
+    pub fn total_capacity(cid: &CurrencyIdentifier) -> u32 {
+        (0..Self::locations(cid).len() as LocationIndexType).fold(0u32, |total, i| {
+            total.saturating_add(Self::capacity_of_existing(cid, i))
+        })
+    }
+
+    /// capacity of a location known to exist
+    fn capacity_of_existing(cid: &CurrencyIdentifier, location_index: LocationIndexType) -> u32 {
+        match Self::location_metadata(cid, location_index) {
+            Some(metadata) if metadata.max_participants > 0 => metadata.max_participants,
+            _ => T::DefaultLocationCapacity::get(),
+        }
+    }
+
+    fn ensure_bootstrapper(who: &T::AccountId, cid: &CurrencyIdentifier) -> Result<(), Error<T>> {
+        ensure!(Self::currency_identifiers().contains(cid), <Error<T>>::CurrencyNotRegistered);
+        ensure!(Self::bootstrappers(cid).contains(who), <Error<T>>::NotBootstrapper);
+        Ok(())
+    }
+
+    fn rejection_error(reason: &LocationRejectionReason) -> Error<T> {
+        match reason {
+            LocationRejectionReason::InvalidCoordinate => <Error<T>>::InvalidGeolocation,
+            LocationRejectionReason::NearPole => <Error<T>>::MinimumDistanceViolationToPole,
+            LocationRejectionReason::TooCloseToSibling(_) => {
+                <Error<T>>::MinimumSolarTripTimeViolatedWithinCurrency
+            }
+            LocationRejectionReason::TooCloseToCurrency(_, _) => {
+                <Error<T>>::MinimumDistanceViolationToOtherCurrency
+            }
+            LocationRejectionReason::BelowMinimumDistanceToSibling(_)
+            | LocationRejectionReason::BelowMinimumDistanceToCurrency(_, _) => {
+                <Error<T>>::MinimumLocationDistanceViolated
+            }
+            LocationRejectionReason::InsideForeignRegion(_) => {
+                <Error<T>>::LocationInsideForeignRegion
+            }
+            LocationRejectionReason::GeoComputationFailed => <Error<T>>::GeoComputationFailed,
+        }
+    }
+
+    /// Trip time of an adversary between two meetups minus the time between those meetups.
+    ///
+    /// Meetups take place at the same local solar time of the same ceremony date, see
+    /// `meetup_timestamp`. The date changes at `CEREMONY_DATE_LINE_LON`, not at the
+    /// antimeridian, so the time between meetups follows the wrapped longitude difference,
+    /// e.g. across the antimeridian within Fiji. Only meetups on either side of the ceremony
+    /// date line happen almost 24h apart.
+    fn solar_trip_time(from: &Location, to: &Location) -> Result<i32, GeoError> {
+        let d = Module::<T>::haversine_distance(&from, &to)? as i32;
+        let dt = Self::ceremony_lon(from.lon)?
+            .checked_sub(Self::ceremony_lon(to.lon)?)
+            .and_then(|dlon| dlon.checked_mul(Degree::from_num(SOLAR_SECONDS_PER_DEGREE)))
+            .ok_or(GeoError::Overflow)?;
+        let tflight = d / MAX_SPEED_MPS;
+        let dt: i32 = dt.checked_abs().ok_or(GeoError::Overflow)?.lossy_into();
+        Ok(tflight - dt)
     }
 
     pub fn is_valid_geolocation(loc: &Location) -> bool {
-        if loc.lat > NORTH_POLE.lat {
+        loc.validate().is_ok()
+    }
+
+    /// true if `loc` lies inside or on the boundary of `region`. false for empty regions
+    pub fn is_inside_region(loc: &Location, region: &[Location]) -> bool {
+        if region.len() < 3 {
             return false;
         }
-        if loc.lat < SOUTH_POLE.lat {
-            return false;
+        let edges = Self::polygon_edges(region);
+        if edges.clone().any(|(a, b)| Self::is_on_segment(loc, a, b)) {
+            return true;
         }
-        if loc.lon > DATELINE_LON {
-            return false;
+        // cast a ray from loc towards east and count crossed edges
+        let (x, y) = (I64F64::from(loc.lon), I64F64::from(loc.lat));
+        edges
+            .filter(|(a, b)| (a.lat > loc.lat) != (b.lat > loc.lat))
+            .filter(|(a, b)| {
+                let (ax, ay) = (I64F64::from(a.lon), I64F64::from(a.lat));
+                let (bx, by) = (I64F64::from(b.lon), I64F64::from(b.lat));
+                x < ax + (y - ay) * (bx - ax) / (by - ay)
+            })
+            .count()
+            % 2
+            == 1
+    }
+
+    /// true if the polygon has no repeated vertices and no intersecting non-adjacent edges
+    fn is_simple_polygon(polygon: &[Location]) -> bool {
+        let n = polygon.len();
+        for (i, (a1, a2)) in Self::polygon_edges(polygon).enumerate() {
+            if a1 == a2 {
+                return false;
+            }
+            for (j, (b1, b2)) in Self::polygon_edges(polygon).enumerate().skip(i + 1) {
+                // adjacent edges share a vertex and may only overlap in it
+                if j == i + 1 || (i == 0 && j == n - 1) {
+                    let (shared, u, v) = if j == i + 1 { (a2, a1, b2) } else { (a1, a2, b1) };
+                    if Self::is_on_segment(u, shared, v) || Self::is_on_segment(v, shared, u) {
+                        return false;
+                    }
+                    continue;
+                }
+                if Self::segments_intersect(a1, a2, b1, b2) {
+                    return false;
+                }
+            }
         }
-        if loc.lon < -DATELINE_LON {
+        true
+    }
+
+    fn regions_overlap(a: &[Location], b: &[Location]) -> bool {
+        if a.len() < 3 || b.len() < 3 {
             return false;
         }
-        true
+        Self::polygon_edges(a).any(|(a1, a2)| {
+            Self::polygon_edges(b).any(|(b1, b2)| Self::segments_intersect(a1, a2, b1, b2))
+        }) || Self::is_inside_region(&a[0], b)
+            || Self::is_inside_region(&b[0], a)
+    }
+
+    fn polygon_edges<'a>(
+        polygon: &'a [Location],
+    ) -> impl Iterator<Item = (&'a Location, &'a Location)> + Clone + 'a {
+        polygon.iter().zip(polygon.iter().cycle().skip(1))
+    }
+
+    /// sign of the cross product (b - a) x (c - a) in the lon/lat plane
+    fn orientation(a: &Location, b: &Location, c: &Location) -> i8 {
+        let cross = (I64F64::from(b.lon) - I64F64::from(a.lon))
+            * (I64F64::from(c.lat) - I64F64::from(a.lat))
+            - (I64F64::from(b.lat) - I64F64::from(a.lat))
+                * (I64F64::from(c.lon) - I64F64::from(a.lon));
+        if cross > I64F64::from_num(0) {
+            1
+        } else if cross < I64F64::from_num(0) {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// true if `p` lies on the segment from `a` to `b`
+    fn is_on_segment(p: &Location, a: &Location, b: &Location) -> bool {
+        Self::orientation(a, b, p) == 0
+            && p.lon >= a.lon.min(b.lon)
+            && p.lon <= a.lon.max(b.lon)
+            && p.lat >= a.lat.min(b.lat)
+            && p.lat <= a.lat.max(b.lat)
+    }
+
+    fn segments_intersect(a1: &Location, a2: &Location, b1: &Location, b2: &Location) -> bool {
+        let (o1, o2) = (Self::orientation(a1, a2, b1), Self::orientation(a1, a2, b2));
+        let (o3, o4) = (Self::orientation(b1, b2, a1), Self::orientation(b1, b2, a2));
+        (o1 * o2 < 0 && o3 * o4 < 0)
+            || Self::is_on_segment(b1, a1, a2)
+            || Self::is_on_segment(b2, a1, a2)
+            || Self::is_on_segment(a1, b1, b2)
+            || Self::is_on_segment(a2, b1, b2)
+    }
+
+    /// offset of local solar time to UTC at `loc` in seconds, positive east of Greenwich.
+    /// West of the antimeridian up to `CEREMONY_DATE_LINE_LON`, the offset exceeds 12h,
+    /// like the time zones of Samoa and Tonga.
+    /// Fails with `GeoError::Overflow` for longitudes far outside of [-180°, 180°]
+    pub fn local_solar_offset_seconds(loc: &Location) -> Result<i32, GeoError> {
+        let offset = Self::ceremony_lon(loc.lon)?
+            .checked_mul(Degree::from_num(SOLAR_SECONDS_PER_DEGREE))
+            .and_then(|offset| offset.checked_round())
+            .ok_or(GeoError::Overflow)?;
+        Ok(offset.lossy_into())
+    }
+
+    /// UTC timestamp [ms] of a meetup at `loc`, given the UTC timestamp `reference_utc_ms` of
+    /// the meetup at the prime meridian on the same ceremony date. Meetups take place at the
+    /// same local solar time everywhere, so they happen earlier east of Greenwich, and earliest
+    /// just west of `CEREMONY_DATE_LINE_LON`.
+    pub fn meetup_timestamp(loc: &Location, reference_utc_ms: u64) -> Result<u64, GeoError> {
+        let offset_ms = i64::from(Self::local_solar_offset_seconds(loc)?) * 1000;
+        if offset_ms >= 0 {
+            Ok(reference_utc_ms.saturating_sub(offset_ms.unsigned_abs()))
+        } else {
+            Ok(reference_utc_ms.saturating_add(offset_ms.unsigned_abs()))
+        }
+    }
+
+    /// longitude difference `a - b` wrapped into [-180°, 180°]
+    fn lon_difference(a: Degree, b: Degree) -> Result<Degree, GeoError> {
+        let d = a.checked_sub(b).ok_or(GeoError::Overflow)?;
+        if d > DATELINE_LON {
+            d.checked_sub(FULL_TURN).ok_or(GeoError::Overflow)
+        } else if d < -DATELINE_LON {
+            d.checked_add(FULL_TURN).ok_or(GeoError::Overflow)
+        } else {
+            Ok(d)
+        }
+    }
+
+    /// longitude east of the ceremony date line, in [-171°, 189°) for valid locations
+    fn ceremony_lon(lon: Degree) -> Result<Degree, GeoError> {
+        if lon < CEREMONY_DATE_LINE_LON {
+            lon.checked_add(FULL_TURN).ok_or(GeoError::Overflow)
+        } else {
+            Ok(lon)
+        }
     }
 
-    pub fn haversine_distance(a: &Location, b: &Location) -> u32 {
+    /// great-circle distance in meters on a spherical earth
+    ///
+    /// Compared to a f64 reference, the result is accurate to 500m + 0.1% for distances up to
+    /// a quarter meridian (~10'000km). The haversine term is quantized to 2^-32, which would
+    /// only resolve steps of ~194m for short distances. Therefore, locations less than
+    /// `EQUIRECTANGULAR_MAX_DEGREES` apart in latitude and longitude use
+    /// `equirectangular_distance` instead, which is accurate to a few centimeters. At that
+    /// threshold, both methods agree to within `MinLocationDistanceM`, so the distance doesn't
+    /// jump where the minimum distance rule applies.
+    /// Beyond a quarter meridian, `asin` loses accuracy and the result
+    /// underestimates the true distance. The worst case is ~36% for antipodes, where the
+    /// result saturates at ~12'742km (two earth radii) instead of ~20'015km.
+    pub fn haversine_distance(a: &Location, b: &Location) -> Result<u32, GeoError> {
+        let delta_lat = a
+            .lat
+            .checked_sub(b.lat)
+            .and_then(|d| d.checked_abs())
+            .ok_or(GeoError::Overflow)?;
+        let delta_lon = Self::lon_difference(a.lon, b.lon)?;
+        if delta_lat < EQUIRECTANGULAR_MAX_DEGREES
+            && delta_lon.checked_abs().ok_or(GeoError::Overflow)? < EQUIRECTANGULAR_MAX_DEGREES
+        {
+            return Self::equirectangular_distance(a, b);
+        }
         type I = I32F32;
         let two = I::from_num(2);
         let theta1 = I::from(a.lat) * I::lossy_from(RADIANS_PER_DEGREE);
         let theta2 = I::from(b.lat) * I::lossy_from(RADIANS_PER_DEGREE);
-        let delta_theta = theta1 - theta2;
-        let delta_lambda = I::from(a.lon - b.lon) * I::lossy_from(RADIANS_PER_DEGREE);
+        let delta_theta = theta1.checked_sub(theta2).ok_or(GeoError::Overflow)?;
+        let delta_lambda = I::from(delta_lon) * I::lossy_from(RADIANS_PER_DEGREE);
         let tmp0 = sin(delta_theta / two);
-        let tmp1 = if let Ok(r) = powi::<I, I>(tmp0, 2) {
-            r
-        } else {
-            I::from_num(0)
-        };
+        let tmp1 = powi::<I, I>(tmp0, 2).map_err(|_| GeoError::TranscendentalFunctionFailed)?;
         let tmp2 = cos(theta1) * cos(theta2);
         let tmp3 = sin(delta_lambda / two);
-        let tmp4 = if let Ok(r) = powi::<I, I>(tmp3, 2) {
-            r
-        } else {
-            I::from_num(0)
-        };
-        let aa = tmp1 + tmp2 * tmp4;
-        let c: I = two * asin(sqrt::<I, I>(aa).unwrap());
-        let d = I::from(MEAN_EARTH_RADIUS) * c;
+        let tmp4 = powi::<I, I>(tmp3, 2).map_err(|_| GeoError::TranscendentalFunctionFailed)?;
+        // rounding may push aa slightly outside [0, 1] close to poles and antipodes
+        let aa = (tmp1 + tmp2 * tmp4).max(I::from_num(0)).min(I::from_num(1));
+        let c: I = two
+            * asin(sqrt::<I, I>(aa).map_err(|_| GeoError::TranscendentalFunctionFailed)?);
+        let d = I::from(MEAN_EARTH_RADIUS)
+            .checked_mul(c)
+            .ok_or(GeoError::Overflow)?;
         let d: i64 = d.lossy_into();
-        d as u32
+        Ok(d as u32)
+    }
+
+    /// distance in meters of nearby locations, projected onto a plane at their mean latitude.
+    /// Computed in I64F64, so the squared angles don't lose resolution
+    fn equirectangular_distance(a: &Location, b: &Location) -> Result<u32, GeoError> {
+        type J = I64F64;
+        let rad = J::from_num(RADIANS_PER_DEGREE);
+        let delta_lat = (J::from_num(a.lat) - J::from_num(b.lat)) * rad;
+        let delta_lon = J::from_num(Self::lon_difference(a.lon, b.lon)?) * rad;
+        let mean_lat = (J::from_num(a.lat) + J::from_num(b.lat)) / J::from_num(2) * rad;
+        let x = delta_lon * J::from_num(cos(I32F32::from_num(mean_lat)));
+        let c = sqrt::<J, J>(x * x + delta_lat * delta_lat)
+            .map_err(|_| GeoError::TranscendentalFunctionFailed)?;
+        let d = (J::from_num(MEAN_EARTH_RADIUS) * c).round();
+        Ok(d.to_num::<u32>())
+    }
+}
+
+impl<T: Trait> CurrencyIssuer<T::AccountId> for Module<T> {
+    fn nominal_income(cid: &CurrencyIdentifier) -> BalanceType {
+        Self::currency_properties(cid).nominal_income
+    }
+
+    fn issue(
+        cid: &CurrencyIdentifier,
+        beneficiaries: &[T::AccountId],
+        amount: BalanceType,
+    ) -> DispatchResult {
+        Self::do_issue(cid, beneficiaries, amount)?;
+        for who in beneficiaries {
+            Self::deposit_event(RawEvent::Issued(*cid, who.clone(), amount));
+        }
+        Ok(())
+    }
+}
+
+/// `Module` has an inherent `total_issuance(&cid)` and a `transfer` dispatchable, which take
+/// precedence over the trait functions of the same name. Call those through
+/// `<Module<T> as MultiCurrency<_>>::`.
+impl<T: Trait> MultiCurrency<T::AccountId> for Module<T> {
+    type CurrencyId = CurrencyIdentifier;
+    type Balance = BalanceType;
+
+    fn total_issuance(currency_id: Self::CurrencyId) -> Self::Balance {
+        Self::total_issuance(&currency_id)
+    }
+
+    fn free_balance(currency_id: Self::CurrencyId, who: &T::AccountId) -> Self::Balance {
+        Self::balance(&currency_id, who)
+    }
+
+    fn transfer(
+        currency_id: Self::CurrencyId,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        ensure!(
+            Self::currency_identifiers().contains(&currency_id),
+            <Error<T>>::CurrencyNotRegistered
+        );
+        Self::do_transfer(&currency_id, from, to, amount)?;
+        Self::deposit_event(RawEvent::Transferred(currency_id, from.clone(), to.clone(), amount));
+        Ok(())
+    }
+
+    fn deposit(
+        currency_id: Self::CurrencyId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        Self::do_issue(&currency_id, rstd::slice::from_ref(who), amount)?;
+        Self::deposit_event(RawEvent::Issued(currency_id, who.clone(), amount));
+        Ok(())
+    }
+
+    fn withdraw(
+        currency_id: Self::CurrencyId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        Self::do_withdraw(&currency_id, who, amount)?;
+        Self::deposit_event(RawEvent::Burned(currency_id, who.clone(), amount));
+        Ok(())
+    }
+}
+
+/// Pays the fees of a signed transaction in a community currency if one is specified, and
+/// with the wrapped extension `N` for native fees, e.g. `ChargeTransactionPayment`, otherwise.
+/// Runtimes use it in place of `N`, so fees are never charged twice.
+///
+/// The fee in native units is `TransactionBaseFee + TransactionByteFee * len + WeightToFee(weight)`,
+/// without the fee multiplier of the transaction payment module. It is converted at the
+/// `FeeConversionRate` of the currency, withdrawn from the demurrage ledger and passed to
+/// `OnFeePaid`. Calls which don't pay fees are free. Like the native fee, the fee in native
+/// units is the priority of the transaction.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeCommunityCurrency<T: Trait + Send + Sync, N>(
+    Option<CurrencyIdentifier>,
+    N,
+    PhantomData<T>,
+);
+
+impl<T: Trait + Send + Sync, N> ChargeCommunityCurrency<T, N> {
+    /// pay fees in `cid`, or with `native` if `None`
+    pub fn new(cid: Option<CurrencyIdentifier>, native: N) -> Self {
+        Self(cid, native, PhantomData)
+    }
+
+    /// fee in `cid` for a call with `info` and encoded length `len`
+    pub fn compute_fee(
+        cid: &CurrencyIdentifier,
+        info: DispatchInfo,
+        len: usize,
+    ) -> Result<BalanceType, TransactionValidityError> {
+        let rate = <Module<T>>::fee_conversion_rate(cid).ok_or(InvalidTransaction::Payment)?;
+        BalanceType::checked_from_num(Self::native_fee(info, len))
+            .and_then(|fee| fee.checked_mul(rate))
+            .ok_or_else(|| InvalidTransaction::Payment.into())
+    }
+
+    /// fee in native units for a call with `info` and encoded length `len`
+    pub fn native_fee(info: DispatchInfo, len: usize) -> u64 {
+        if !info.pays_fee {
+            return 0;
+        }
+        T::TransactionBaseFee::get()
+            .saturating_add(T::TransactionByteFee::get().saturating_mul(len as u64))
+            .saturating_add(T::WeightToFee::convert(info.weight))
+    }
+}
+
+impl<T: Trait + Send + Sync, N: fmt::Debug> fmt::Debug for ChargeCommunityCurrency<T, N> {
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ChargeCommunityCurrency<{:?}, {:?}>", self.0, self.1)
+    }
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl<T, N> SignedExtension for ChargeCommunityCurrency<T, N>
+where
+    T: Trait + Send + Sync,
+    N: SignedExtension<
+        AccountId = T::AccountId,
+        Call = T::Call,
+        DispatchInfo = DispatchInfo,
+        Pre = (),
+    >,
+{
+    const IDENTIFIER: &'static str = "ChargeCommunityCurrency";
+    type AccountId = T::AccountId;
+    type Call = T::Call;
+    type AdditionalSigned = N::AdditionalSigned;
+    type DispatchInfo = DispatchInfo;
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<N::AdditionalSigned, TransactionValidityError> {
+        self.1.additional_signed()
+    }
+
+    fn validate(
+        &self,
+        who: &Self::AccountId,
+        call: &Self::Call,
+        info: Self::DispatchInfo,
+        len: usize,
+    ) -> TransactionValidity {
+        match &self.0 {
+            Some(cid) => {
+                let fee = Self::compute_fee(cid, info, len)?;
+                if fee > BalanceType::from_num(0) {
+                    // same checks as the withdrawal in pre_dispatch
+                    <Module<T>>::balance_entry_after_withdrawal(cid, who, fee)
+                        .map_err(|_| InvalidTransaction::Payment)?;
+                }
+                Ok(ValidTransaction {
+                    priority: Self::native_fee(info, len),
+                    ..Default::default()
+                })
+            }
+            None => self.1.validate(who, call, info, len),
+        }
+    }
+
+    fn pre_dispatch(
+        self,
+        who: &Self::AccountId,
+        call: &Self::Call,
+        info: Self::DispatchInfo,
+        len: usize,
+    ) -> Result<(), TransactionValidityError> {
+        match &self.0 {
+            Some(cid) => {
+                let fee = Self::compute_fee(cid, info, len)?;
+                if fee > BalanceType::from_num(0) {
+                    <Module<T>>::do_withdraw(cid, who, fee)
+                        .map_err(|_| InvalidTransaction::Payment)?;
+                    <Module<T>>::deposit_event(RawEvent::Burned(*cid, who.clone(), fee));
+                    T::OnFeePaid::on_fee_paid(cid, fee);
+                }
+                Ok(())
+            }
+            None => self.1.pre_dispatch(who, call, info, len),
+        }
+    }
+}
+
+impl<T: Trait> MultiReservableCurrency<T::AccountId> for Module<T> {
+    fn reserved_balance(currency_id: Self::CurrencyId, who: &T::AccountId) -> Self::Balance {
+        Self::reserved_balance(&currency_id, who)
+    }
+
+    fn reserve(
+        currency_id: Self::CurrencyId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        Self::do_reserve(&currency_id, who, amount)?;
+        Ok(())
+    }
+
+    fn unreserve(
+        currency_id: Self::CurrencyId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> Self::Balance {
+        Self::do_unreserve(&currency_id, who, amount)
+    }
+}
+
+impl<T: Trait> MultiLockableCurrency<T::AccountId> for Module<T> {
+    fn set_lock(
+        lock_id: LockIdentifier,
+        currency_id: Self::CurrencyId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        Self::do_set_lock(lock_id, &currency_id, who, amount)?;
+        Ok(())
+    }
+
+    fn remove_lock(lock_id: LockIdentifier, currency_id: Self::CurrencyId, who: &T::AccountId) {
+        Self::do_remove_lock(lock_id, &currency_id, who)
     }
 }
 
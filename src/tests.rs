@@ -16,7 +16,7 @@ use super::*;
 use crate::{GenesisConfig, Module, Trait};
 use externalities::set_and_run_with_externalities;
 use primitives::{hashing::blake2_256, sr25519, Blake2Hasher, Pair, Public, H256};
-use sp_runtime::traits::{CheckedAdd, IdentifyAccount, Member, Verify};
+use sp_runtime::traits::{CheckedAdd, ConvertInto, IdentifyAccount, Member, Verify};
 use sp_runtime::{
     testing::Header,
     traits::{BlakeTwo256, IdentityLookup},
@@ -24,8 +24,9 @@ use sp_runtime::{
 };
 use std::{cell::RefCell, collections::HashSet};
 use support::traits::{Currency, FindAuthor, Get, LockIdentifier};
-use support::{assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
+use support::{assert_err, assert_noop, assert_ok, impl_outer_event, impl_outer_origin, parameter_types};
 use sp_keyring::AccountKeyring;
+use proptest::prelude::*;
 
 use fixed::traits::LossyFrom;
 use fixed::types::{I32F32, I9F23, I9F55};
@@ -41,6 +42,9 @@ pub type AccountId = <Signature as Verify>::Signer;
 
 thread_local! {
     static EXISTENTIAL_DEPOSIT: RefCell<u64> = RefCell::new(1);
+    static MIN_LOCATION_DISTANCE_M: RefCell<u32> = RefCell::new(100);
+    static NATIVE_FEES_CHARGED: RefCell<u32> = RefCell::new(0);
+    static COMMUNITY_FEES_PAID: RefCell<Vec<(CurrencyIdentifier, BalanceType)>> = RefCell::new(vec![]);
 }
 pub type BlockNumber = u64;
 pub type Balance = u64;
@@ -55,8 +59,61 @@ impl Get<u64> for ExistentialDeposit {
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct TestRuntime;
 
+pub struct MinLocationDistanceM;
+impl Get<u32> for MinLocationDistanceM {
+    fn get() -> u32 {
+        MIN_LOCATION_DISTANCE_M.with(|v| *v.borrow())
+    }
+}
+
+pub struct RecordCommunityFees;
+impl OnCommunityFeePaid for RecordCommunityFees {
+    fn on_fee_paid(cid: &CurrencyIdentifier, fee: BalanceType) {
+        COMMUNITY_FEES_PAID.with(|v| v.borrow_mut().push((*cid, fee)));
+    }
+}
+
+/// stands in for `ChargeTransactionPayment`, counting the native fees it charges
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Debug)]
+pub struct ChargeNativeFee;
+impl SignedExtension for ChargeNativeFee {
+    const IDENTIFIER: &'static str = "ChargeNativeFee";
+    type AccountId = AccountId;
+    type Call = ();
+    type AdditionalSigned = ();
+    type DispatchInfo = DispatchInfo;
+    type Pre = ();
+    fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+        Ok(())
+    }
+    fn pre_dispatch(
+        self,
+        _who: &AccountId,
+        _call: &(),
+        _info: DispatchInfo,
+        _len: usize,
+    ) -> Result<(), TransactionValidityError> {
+        NATIVE_FEES_CHARGED.with(|v| *v.borrow_mut() += 1);
+        Ok(())
+    }
+}
+
+parameter_types! {
+    pub const DefaultLocationCapacity: u32 = 10;
+    pub const ExpectedBlockTimeMs: u64 = 5_000;
+    pub const TransactionBaseFee: u64 = 10;
+    pub const TransactionByteFee: u64 = 1;
+}
+
 impl Trait for TestRuntime {
     type Event = ();
+    type MinLocationDistanceM = MinLocationDistanceM;
+    type DefaultLocationCapacity = DefaultLocationCapacity;
+    type ExpectedBlockTimeMs = ExpectedBlockTimeMs;
+    type TransactionBaseFee = TransactionBaseFee;
+    type TransactionByteFee = TransactionByteFee;
+    type WeightToFee = ConvertInto;
+    type OnFeePaid = RecordCommunityFees;
 }
 
 pub type EncointerCurrencies = Module<TestRuntime>;
@@ -94,8 +151,6 @@ pub type System = system::Module<TestRuntime>;
 parameter_types! {
     pub const TransferFee: Balance = 0;
     pub const CreationFee: Balance = 0;
-    pub const TransactionBaseFee: u64 = 0;
-    pub const TransactionByteFee: u64 = 0;
 }
 impl balances::Trait for TestRuntime {
     type Balance = Balance;
@@ -139,6 +194,28 @@ fn get_accountid(pair: &sr25519::Pair) -> AccountId {
 
 type T = Degree;
 
+fn loc(lat: f64, lon: f64) -> Location {
+    Location::from_decimal_degrees(lat, lon).unwrap()
+}
+
+fn test_bootstrappers() -> Vec<AccountId> {
+    vec![
+        AccountId::from(AccountKeyring::Alice),
+        AccountId::from(AccountKeyring::Bob),
+        AccountId::from(AccountKeyring::Charlie),
+    ]
+}
+
+fn register_test_currency(locations: Vec<Location>) -> CurrencyIdentifier {
+    let bs = test_bootstrappers();
+    assert_ok!(EncointerCurrencies::new_currency(
+        Origin::signed(bs[0].clone()),
+        locations.clone(),
+        bs.clone()
+    ));
+    CurrencyIdentifier::from(blake2_256(&(locations, bs).encode()))
+}
+
 #[test]
 fn solar_trip_time_works() {
     // one degree equator
@@ -150,8 +227,8 @@ fn solar_trip_time_works() {
         lat: T::from_num(0i32),
         lon: T::from_num(1i32),
     }; // one degree lat is 111km at the equator
-    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b), 1099);
-    assert_eq!(EncointerCurrencies::solar_trip_time(&b, &a), 1099);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b).unwrap(), 1099);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&b, &a).unwrap(), 1099);
     // Reykjavik one degree lon: expect to yield much shorter times than at the equator
     let a = Location {
         lat: T::from_num(64.135480_f64),
@@ -161,7 +238,7 @@ fn solar_trip_time_works() {
         lat: T::from_num(64.135_480),
         lon: T::from_num(-20.895410),
     };
-    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b), 344);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b).unwrap(), 344);
 
     // Reykjavik 111km: expect to yield much shorter times than at the equator because
     // next time zone is much closer in meter overland.
@@ -174,7 +251,7 @@ fn solar_trip_time_works() {
         lat: T::from_num(64.135480_f64),
         lon: T::from_num(2.290000_f64),
     }; // 2.29° is 111km
-    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b), 789);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b).unwrap(), 789);
     // maximal
     let a = Location {
         lat: T::from_num(0i32),
@@ -184,8 +261,58 @@ fn solar_trip_time_works() {
         lat: T::from_num(0i32),
         lon: T::from_num(180i32),
     };
-    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b), 110318);
-    assert_eq!(EncointerCurrencies::solar_trip_time(&b, &a), 110318);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&a, &b).unwrap(), 110318);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&b, &a).unwrap(), 110318);
+}
+
+#[test]
+fn local_solar_offset_seconds_works() {
+    let offset = |l: Location| EncointerCurrencies::local_solar_offset_seconds(&l).unwrap();
+    assert_eq!(offset(loc(0.0, 0.0)), 0);
+    assert_eq!(offset(loc(47.0, 15.0)), 3600);
+    assert_eq!(offset(loc(40.0, -75.0)), -18000);
+    // Zurich
+    assert_eq!(offset(loc(47.3769, 8.5417)), 2050);
+    assert_eq!(offset(loc(-16.875, 180.0)), 43200);
+    assert_eq!(offset(loc(-16.875, -180.0)), 43200);
+    // Apia (Samoa) and Pago Pago (American Samoa) on either side of the ceremony date line
+    assert_eq!(offset(loc(-13.8125, -171.75)), 45180);
+    assert_eq!(offset(loc(-14.25, -170.6875)), -40965);
+    // unvalidated locations may be out of range
+    let far_east = Location {
+        lat: T::from_num(0),
+        lon: T::from_num(10_000_000),
+    };
+    assert_eq!(
+        EncointerCurrencies::local_solar_offset_seconds(&far_east),
+        Err(GeoError::Overflow)
+    );
+    assert_eq!(
+        EncointerCurrencies::meetup_timestamp(&far_east, 0),
+        Err(GeoError::Overflow)
+    );
+}
+
+#[test]
+fn meetup_timestamp_works() {
+    let meetup = |l: Location, reference: u64| {
+        EncointerCurrencies::meetup_timestamp(&l, reference).unwrap()
+    };
+    // 2020-06-01T12:00:00Z
+    let reference: u64 = 1_591_012_800_000;
+    assert_eq!(meetup(loc(51.4769, 0.0), reference), reference);
+    assert_eq!(meetup(loc(47.0, 15.0), reference), reference - 3_600_000);
+    assert_eq!(meetup(loc(40.0, -75.0), reference), reference + 18_000_000);
+    // across the antimeridian, meetups follow the sun
+    let taveuni = meetup(loc(-16.875, 179.875), reference);
+    let vanua_balavu = meetup(loc(-16.5, -179.875), reference);
+    assert_eq!(taveuni - vanua_balavu, 60_000);
+    // across the ceremony date line, meetups are almost 24h apart
+    let apia = meetup(loc(-13.8125, -171.75), reference);
+    let pago_pago = meetup(loc(-14.25, -170.6875), reference);
+    assert_eq!(pago_pago - apia, 86_145_000);
+    // saturates instead of underflowing
+    assert_eq!(meetup(loc(0.0, 90.0), 0), 0);
 }
 
 #[test]
@@ -203,7 +330,7 @@ fn haversine_distance_works() {
             lon: T::from_num(1),
         };
         assert_abs_diff_eq!(
-            f64::from(EncointerCurrencies::haversine_distance(&a, &b) as i32) * 0.001,
+            f64::from(EncointerCurrencies::haversine_distance(&a, &b).unwrap() as i32) * 0.001,
             111111.0 * 0.001,
             epsilon = 0.1
         );
@@ -218,21 +345,349 @@ fn haversine_distance_works() {
             lon: T::from_num(180),
         };
         assert_abs_diff_eq!(
-            f64::from(EncointerCurrencies::haversine_distance(&a, &b) as i32) * 0.001,
+            f64::from(EncointerCurrencies::haversine_distance(&a, &b).unwrap() as i32) * 0.001,
             12742.0,
             epsilon = 0.1
         );
 
         // pole to pole
         assert_abs_diff_eq!(
-            f64::from(EncointerCurrencies::haversine_distance(&NORTH_POLE, &SOUTH_POLE) as i32)
-                * 0.001,
+            f64::from(
+                EncointerCurrencies::haversine_distance(&NORTH_POLE, &SOUTH_POLE).unwrap() as i32
+            ) * 0.001,
             12742.0,
             epsilon = 0.1
         );
     });
 }
 
+// quarter meridian in [m]: accuracy bounds of haversine_distance hold up to this distance
+const QUARTER_MERIDIAN_M: f64 = 10_007_543.0;
+// half circumference in [m]: no distance may exceed this
+const HALF_CIRCUMFERENCE_M: f64 = 20_015_087.0;
+
+fn haversine_distance_f64(a: &Location, b: &Location) -> f64 {
+    let (lat1, lon1) = (a.lat.to_num::<f64>().to_radians(), a.lon.to_num::<f64>().to_radians());
+    let (lat2, lon2) = (b.lat.to_num::<f64>().to_radians(), b.lon.to_num::<f64>().to_radians());
+    let aa = ((lat1 - lat2) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon1 - lon2) / 2.0).sin().powi(2);
+    2.0 * 6_371_000.0 * aa.sqrt().asin()
+}
+
+proptest! {
+    #[test]
+    fn haversine_distance_is_accurate(
+        lat1 in -90.0f64..90.0, lon1 in -180.0f64..180.0,
+        lat2 in -90.0f64..90.0, lon2 in -180.0f64..180.0,
+    ) {
+        let a = Location { lat: T::from_num(lat1), lon: T::from_num(lon1) };
+        let b = Location { lat: T::from_num(lat2), lon: T::from_num(lon2) };
+        let d = f64::from(EncointerCurrencies::haversine_distance(&a, &b).unwrap());
+        let d_ref = haversine_distance_f64(&a, &b);
+        if d_ref <= QUARTER_MERIDIAN_M {
+            prop_assert!(
+                (d - d_ref).abs() <= 500.0 + 0.001 * d_ref,
+                "{:?} to {:?}: {}m instead of {}m", a, b, d, d_ref
+            );
+        } else {
+            // documented worst case: antipodes come out as two earth radii
+            prop_assert!(
+                d >= 0.63 * d_ref && d <= d_ref + 500.0 + 0.001 * d_ref,
+                "{:?} to {:?}: {}m instead of {}m", a, b, d, d_ref
+            );
+            prop_assert!(d <= HALF_CIRCUMFERENCE_M);
+        }
+    }
+
+    #[test]
+    fn haversine_distance_is_symmetric(
+        lat1 in -90.0f64..90.0, lon1 in -180.0f64..180.0,
+        lat2 in -90.0f64..90.0, lon2 in -180.0f64..180.0,
+    ) {
+        let a = Location { lat: T::from_num(lat1), lon: T::from_num(lon1) };
+        let b = Location { lat: T::from_num(lat2), lon: T::from_num(lon2) };
+        let d_ab = i64::from(EncointerCurrencies::haversine_distance(&a, &b).unwrap());
+        let d_ba = i64::from(EncointerCurrencies::haversine_distance(&b, &a).unwrap());
+        prop_assert!((d_ab - d_ba).abs() <= 1);
+    }
+
+    #[test]
+    fn solar_trip_time_is_defined_everywhere(
+        lat1 in -90.0f64..90.0, lon1 in -180.0f64..180.0,
+        lat2 in -90.0f64..90.0, lon2 in -180.0f64..180.0,
+    ) {
+        let a = Location { lat: T::from_num(lat1), lon: T::from_num(lon1) };
+        let b = Location { lat: T::from_num(lat2), lon: T::from_num(lon2) };
+        prop_assert!(EncointerCurrencies::solar_trip_time(&a, &b).is_ok());
+    }
+}
+
+#[test]
+fn haversine_distance_is_precise_for_short_distances() {
+    let d = |a: Location, b: Location| EncointerCurrencies::haversine_distance(&a, &b).unwrap();
+    // north
+    assert_eq!(d(loc(1.0, 1.0), loc(1.0009, 1.0)), 100);
+    assert_eq!(d(loc(1.0, 1.0), loc(1.0018, 1.0)), 200);
+    // east, where a degree of longitude is half as long
+    assert_eq!(d(loc(60.0, 1.0), loc(60.0, 1.002)), 111);
+    // across the antimeridian
+    assert_eq!(d(loc(0.0, 179.9995), loc(0.0, -179.9995)), 111);
+    assert_eq!(d(loc(1.0, 1.0), loc(1.0, 1.0)), 0);
+}
+
+proptest! {
+    #[test]
+    fn haversine_distance_is_accurate_for_short_distances(
+        lat in -89.0f64..89.0, lon in -180.0f64..180.0,
+        dlat in -0.12f64..0.12, dlon in -0.12f64..0.12,
+    ) {
+        let a = Location { lat: T::from_num(lat), lon: T::from_num(lon) };
+        let b = Location { lat: T::from_num(lat + dlat), lon: T::from_num(lon + dlon) };
+        let d = f64::from(EncointerCurrencies::haversine_distance(&a, &b).unwrap());
+        let d_ref = haversine_distance_f64(&a, &b);
+        prop_assert!(
+            (d - d_ref).abs() <= 1.0,
+            "{:?} to {:?}: {}m instead of {}m", a, b, d, d_ref
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn haversine_distance_is_continuous_at_equirectangular_threshold(
+        lat in -80.0f64..80.0, lon in -179.0f64..179.0, north in proptest::bool::ANY,
+    ) {
+        let a = Location { lat: T::from_num(lat), lon: T::from_num(lon) };
+        let offset = |d: T| if north {
+            Location { lat: a.lat + d, lon: a.lon }
+        } else {
+            Location { lat: a.lat, lon: a.lon + d }
+        };
+        // just below the threshold uses the equirectangular projection, at the threshold haversine
+        let below = offset(EQUIRECTANGULAR_MAX_DEGREES - T::from_bits(1));
+        let at = offset(EQUIRECTANGULAR_MAX_DEGREES);
+        let d_below = i64::from(EncointerCurrencies::haversine_distance(&a, &below).unwrap());
+        let d_at = i64::from(EncointerCurrencies::haversine_distance(&a, &at).unwrap());
+        prop_assert!(
+            (d_at - d_below).abs() < i64::from(MinLocationDistanceM::get()),
+            "{:?}: {}m below and {}m at the threshold", a, d_below, d_at
+        );
+    }
+}
+
+#[test]
+fn haversine_distance_fails_for_out_of_range_locations() {
+    let d = |a: Location, b: Location| EncointerCurrencies::haversine_distance(&a, &b);
+    let max = T::max_value();
+    let min = T::min_value();
+    let zero = T::from_num(0);
+    assert_eq!(
+        d(Location { lat: max, lon: zero }, Location { lat: min, lon: zero }),
+        Err(GeoError::Overflow)
+    );
+    assert_eq!(
+        d(Location { lat: zero, lon: max }, Location { lat: zero, lon: min }),
+        Err(GeoError::Overflow)
+    );
+    assert_eq!(
+        d(Location { lat: min, lon: zero }, Location { lat: zero, lon: zero }),
+        Err(GeoError::Overflow)
+    );
+}
+
+#[test]
+fn haversine_distance_is_defined_at_boundaries() {
+    let boundaries = [-90i32, -89, 0, 89, 90];
+    let meridians = [-180i32, -179, 0, 179, 180];
+    let locations: Vec<Location> = boundaries
+        .iter()
+        .flat_map(|lat| {
+            meridians.iter().map(move |lon| Location {
+                lat: T::from_num(*lat),
+                lon: T::from_num(*lon),
+            })
+        })
+        .collect();
+    for a in locations.iter() {
+        for b in locations.iter() {
+            let d = f64::from(EncointerCurrencies::haversine_distance(a, b).unwrap());
+            assert!(d <= HALF_CIRCUMFERENCE_M);
+            if a == b {
+                assert!(d <= 1.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn canonicalize_works() {
+    let a = Location {
+        lat: T::from_num(47.376_9_f64),
+        lon: T::from_num(8.541_7_f64),
+    };
+    assert!(!a.is_canonical());
+    let c = a.canonicalize();
+    assert!(c.is_canonical());
+    assert_eq!(c.canonicalize(), c);
+    assert!((c.lat - a.lat).abs() <= T::from_bits(1 << 8));
+    assert!((c.lon - a.lon).abs() <= T::from_bits(1 << 8));
+
+    // representation as I9F23 is lossless for canonical locations
+    assert_eq!(T::from(I9F23::from_num(c.lat)), c.lat);
+    assert_eq!(T::from(I9F23::from_num(c.lon)), c.lon);
+
+    let b = Location {
+        lat: T::from_num(-33.5_f64),
+        lon: T::from_num(-70.25_f64),
+    };
+    assert!(b.is_canonical());
+    assert_eq!(b.canonicalize(), b);
+}
+
+#[test]
+fn is_valid_geolocation_rejects_non_canonical_location() {
+    let a = Location {
+        lat: T::from_num(1i32),
+        lon: T::from_num(1i32),
+    };
+    assert!(EncointerCurrencies::is_valid_geolocation(&a));
+    let b = Location {
+        lat: a.lat + T::from_bits(1),
+        lon: a.lon,
+    };
+    assert!(!EncointerCurrencies::is_valid_geolocation(&b));
+    assert_eq!(b.canonicalize(), a);
+}
+
+#[test]
+fn location_from_decimal_degrees_works() {
+    let zurich = Location::from_decimal_degrees(47.3769, 8.5417).unwrap();
+    assert!(zurich.is_canonical());
+    assert_abs_diff_eq!(zurich.lat.to_num::<f64>(), 47.3769, epsilon = 1e-6);
+    assert_abs_diff_eq!(zurich.lon.to_num::<f64>(), 8.5417, epsilon = 1e-6);
+    assert_eq!(
+        Location::from_decimal_degrees(90.5, 0.0),
+        Err(LocationError::InvalidLatitude)
+    );
+    assert_eq!(
+        Location::from_decimal_degrees(0.0, -180.5),
+        Err(LocationError::InvalidLongitude)
+    );
+    assert_eq!(
+        Location::from_decimal_degrees(std::f64::NAN, 0.0),
+        Err(LocationError::InvalidLatitude)
+    );
+}
+
+#[test]
+fn location_from_microdegrees_works() {
+    let zurich = Location::from_microdegrees(47_376_900, 8_541_700).unwrap();
+    assert_eq!(zurich, Location::from_decimal_degrees(47.3769, 8.5417).unwrap());
+    assert_eq!(
+        Location::from_microdegrees(-90_000_000, 180_000_000).unwrap(),
+        Location {
+            lat: T::from_num(-90),
+            lon: T::from_num(180)
+        }
+    );
+    assert_eq!(
+        Location::from_microdegrees(90_000_001, 0),
+        Err(LocationError::InvalidLatitude)
+    );
+    assert_eq!(
+        Location::from_microdegrees(std::i32::MIN, 0),
+        Err(LocationError::InvalidLatitude)
+    );
+    assert_eq!(
+        Location::from_microdegrees(0, std::i32::MIN),
+        Err(LocationError::InvalidLongitude)
+    );
+    assert_eq!(
+        Location::from_microdegrees(0, -180_000_001),
+        Err(LocationError::InvalidLongitude)
+    );
+}
+
+#[test]
+fn location_from_str_works() {
+    let zurich: Location = "47.3769,8.5417".parse().unwrap();
+    assert_eq!(zurich, Location::from_microdegrees(47_376_900, 8_541_700).unwrap());
+    assert_eq!(
+        " -33.5 , -70.25 ".parse::<Location>().unwrap(),
+        Location::new(T::from_num(-33.5), T::from_num(-70.25)).unwrap()
+    );
+    assert_eq!("47.3769".parse::<Location>(), Err(LocationError::InvalidFormat));
+    assert_eq!("1,2,3".parse::<Location>(), Err(LocationError::InvalidFormat));
+    assert_eq!("x,8.5".parse::<Location>(), Err(LocationError::InvalidLatitude));
+    assert_eq!("91,8.5".parse::<Location>(), Err(LocationError::InvalidLatitude));
+    assert_eq!("47,181".parse::<Location>(), Err(LocationError::InvalidLongitude));
+}
+
+#[test]
+fn location_display_roundtrip_works() {
+    let zurich = Location::from_decimal_degrees(47.3769, 8.5417).unwrap();
+    assert_eq!(zurich.to_string().parse::<Location>().unwrap(), zurich);
+    let a = Location::new(T::from_num(-33.5), T::from_num(-70.25)).unwrap();
+    assert_eq!(a.to_string(), "-33.5,-70.25");
+}
+
+#[test]
+fn location_new_validates() {
+    assert!(Location::new(T::from_num(1), T::from_num(1)).is_ok());
+    assert_eq!(
+        Location::new(T::from_num(1) + T::from_bits(1), T::from_num(1)),
+        Err(LocationError::NonCanonical)
+    );
+    assert_eq!(
+        Location::new(T::from_num(-91), T::from_num(1)),
+        Err(LocationError::InvalidLatitude)
+    );
+}
+
+#[test]
+fn to_geohash_works() {
+    let a = Location::from_decimal_degrees(57.64911, 10.40744).unwrap();
+    assert_eq!(a.to_geohash(11), b"u4pruydqqvj".to_vec());
+    assert_eq!(a.to_geohash(3), b"u4p".to_vec());
+    assert_eq!(a.to_geohash(0), b"".to_vec());
+    assert_eq!(a.to_geohash(MAX_GEOHASH_PRECISION + 5).len(), MAX_GEOHASH_PRECISION);
+    let b = Location::from_decimal_degrees(42.605, -5.603).unwrap();
+    assert_eq!(b.to_geohash(5), b"ezs42".to_vec());
+    assert_eq!(NORTH_POLE.to_geohash(2), b"up".to_vec());
+    assert_eq!(SOUTH_POLE.to_geohash(2), b"h0".to_vec());
+}
+
+#[test]
+fn from_geohash_works() {
+    assert_eq!(
+        Location::from_geohash("ezs42").unwrap(),
+        Location::new(T::from_num(42.60498046875), T::from_num(-5.60302734375)).unwrap()
+    );
+    assert_eq!(
+        Location::from_geohash("EZS42").unwrap(),
+        Location::from_geohash("ezs42").unwrap()
+    );
+    assert_eq!(Location::from_geohash(""), Err(LocationError::InvalidGeohash));
+    assert_eq!(Location::from_geohash("ezs4a"), Err(LocationError::InvalidGeohash));
+    assert_eq!(
+        Location::from_geohash("u4pruydqqvjzz"),
+        Err(LocationError::InvalidGeohash)
+    );
+}
+
+#[test]
+fn geohash_roundtrip_works() {
+    let a = Location::from_decimal_degrees(-16.875, 179.875).unwrap();
+    for precision in 1..=MAX_GEOHASH_PRECISION {
+        let hash = a.to_geohash(precision);
+        let b = Location::from_geohash(std::str::from_utf8(&hash).unwrap()).unwrap();
+        assert_eq!(b.to_geohash(precision), hash);
+    }
+    let c = Location::from_geohash(std::str::from_utf8(&a.to_geohash(12)).unwrap()).unwrap();
+    assert!((c.lat - a.lat).abs() < T::from_num(0.000_001));
+    assert!((c.lon - a.lon).abs() < T::from_num(0.000_001));
+}
+
 #[test]
 fn new_currency_works() {
     ExtBuilder::build().execute_with(|| {
@@ -281,7 +736,8 @@ fn new_currency_with_too_close_inner_locations_fails() {
         let b = Location {
             lat: T::from_num(1i32),
             lon: T::from_num(1.000001_f64),
-        };
+        }
+        .canonicalize();
         // a and b roughly 11cm apart
         let loc = vec![a, b];
         let bs = vec![alice.clone(), bob.clone(), charlie.clone()];
@@ -318,11 +774,13 @@ fn new_currency_too_close_to_existing_currency_fails() {
         let a = Location {
             lat: T::from_num(1.000001_f64),
             lon: T::from_num(1.000001_f64),
-        };
+        }
+        .canonicalize();
         let b = Location {
             lat: T::from_num(1.000001_f64),
             lon: T::from_num(2.000001_f64),
-        };
+        }
+        .canonicalize();
         let loc = vec![a, b];
         assert!(EncointerCurrencies::new_currency(
             Origin::signed(alice.clone()),
@@ -333,6 +791,82 @@ fn new_currency_too_close_to_existing_currency_fails() {
     });
 }
 
+#[test]
+fn new_currency_with_non_canonical_locations_fails() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let charlie = AccountId::from(AccountKeyring::Charlie);
+        let bs = vec![alice.clone(), bob.clone(), charlie.clone()];
+        let a = Location {
+            lat: T::from_num(1i32),
+            lon: T::from_num(1i32),
+        };
+        // visually identical to (1°, 2°), but with excess precision
+        let b = Location {
+            lat: T::from_num(1i32),
+            lon: T::from_num(2i32) + T::from_bits(1),
+        };
+        assert_err!(
+            EncointerCurrencies::new_currency(
+                Origin::signed(alice.clone()),
+                vec![a, b],
+                bs.clone()
+            ),
+            Error::<TestRuntime>::InvalidGeolocation
+        );
+        assert_eq!(b.canonicalize(), loc(1.0, 2.0));
+        assert_ok!(EncointerCurrencies::new_currency(
+            Origin::signed(alice.clone()),
+            vec![a, b.canonicalize()],
+            bs
+        ));
+    });
+}
+
+#[test]
+fn new_currency_fails_with_specific_errors() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bs = test_bootstrappers();
+        let a = loc(1.0, 1.0);
+        let b = loc(1.0, 2.0);
+        let invalid = Location {
+            lat: T::from_num(91),
+            lon: T::from_num(2),
+        };
+        assert_err!(
+            EncointerCurrencies::new_currency(
+                Origin::signed(alice.clone()),
+                vec![a, invalid],
+                bs.clone()
+            ),
+            Error::<TestRuntime>::InvalidGeolocation
+        );
+        assert_err!(
+            EncointerCurrencies::new_currency(
+                Origin::signed(alice.clone()),
+                vec![a, b, a],
+                bs.clone()
+            ),
+            Error::<TestRuntime>::MinimumSolarTripTimeViolatedWithinCurrency
+        );
+        assert_err!(
+            EncointerCurrencies::new_currency(
+                Origin::signed(alice.clone()),
+                vec![a, loc(89.0, 2.0)],
+                bs.clone()
+            ),
+            Error::<TestRuntime>::MinimumDistanceViolationToPole
+        );
+        register_test_currency(vec![a, b]);
+        assert_noop!(
+            EncointerCurrencies::new_currency(Origin::signed(alice.clone()), vec![a, b], bs),
+            Error::<TestRuntime>::CurrencyAlreadyRegistered
+        );
+    });
+}
+
 #[test]
 fn new_currency_with_near_pole_locations_fails() {
     ExtBuilder::build().execute_with(|| {
@@ -371,22 +905,108 @@ fn new_currency_with_near_pole_locations_fails() {
 }
 
 #[test]
-fn new_currency_near_dateline_fails() {
+fn solar_trip_time_across_dateline_works() {
+    // Taveuni (Fiji) and Vanua Balavu (Fiji, east of the antimeridian) are roughly 50km apart
+    // and their meetups at the same local solar time are one minute apart
+    let a = Location {
+        lat: T::from_num(-16.875),
+        lon: T::from_num(179.875),
+    };
+    let b = Location {
+        lat: T::from_num(-16.5),
+        lon: T::from_num(-179.875),
+    };
+    assert_abs_diff_eq!(
+        f64::from(EncointerCurrencies::haversine_distance(&a, &b).unwrap() as i32) * 0.001,
+        49.5,
+        epsilon = 0.5
+    );
+    let t = EncointerCurrencies::solar_trip_time(&a, &b).unwrap();
+    assert!(t >= MIN_SOLAR_TRIP_TIME_S);
+    assert_eq!(EncointerCurrencies::solar_trip_time(&b, &a).unwrap(), t);
+
+    // Apia (Samoa) and Pago Pago (American Samoa) are roughly 125km apart, but on either side
+    // of the ceremony date line, so their meetups are almost 24h apart
+    let a = Location {
+        lat: T::from_num(-13.8125),
+        lon: T::from_num(-171.75),
+    };
+    let b = Location {
+        lat: T::from_num(-14.25),
+        lon: T::from_num(-170.6875),
+    };
+    assert!(EncointerCurrencies::solar_trip_time(&a, &b).unwrap() < MIN_SOLAR_TRIP_TIME_S);
+    assert!(EncointerCurrencies::solar_trip_time(&b, &a).unwrap() < MIN_SOLAR_TRIP_TIME_S);
+}
+
+#[test]
+fn new_currency_near_dateline_works() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let charlie = AccountId::from(AccountKeyring::Charlie);
+        let bs = vec![alice.clone(), bob.clone(), charlie.clone()];
+
+        // Suva and Lautoka (Fiji)
+        let a = Location {
+            lat: T::from_num(-18.125),
+            lon: T::from_num(178.4375),
+        };
+        let b = Location {
+            lat: T::from_num(-17.625),
+            lon: T::from_num(177.4375),
+        };
+        let loc = vec![a, b];
+        assert_ok!(EncointerCurrencies::new_currency(
+            Origin::signed(alice.clone()),
+            loc,
+            bs
+        ));
+    });
+}
+
+#[test]
+fn new_currency_across_dateline_works() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let charlie = AccountId::from(AccountKeyring::Charlie);
+        let bs = vec![alice.clone(), bob.clone(), charlie.clone()];
+
+        // Taveuni and Vanua Balavu (Fiji) on either side of the antimeridian
+        let a = Location {
+            lat: T::from_num(-16.875),
+            lon: T::from_num(179.875),
+        };
+        let b = Location {
+            lat: T::from_num(-16.5),
+            lon: T::from_num(-179.875),
+        };
+        let loc = vec![a, b];
+        assert_ok!(EncointerCurrencies::new_currency(
+            Origin::signed(alice.clone()),
+            loc,
+            bs
+        ));
+    });
+}
+
+#[test]
+fn new_currency_across_ceremony_date_line_fails() {
     ExtBuilder::build().execute_with(|| {
-        let master = AccountId::from(AccountKeyring::Alice);
         let alice = AccountId::from(AccountKeyring::Alice);
         let bob = AccountId::from(AccountKeyring::Bob);
         let charlie = AccountId::from(AccountKeyring::Charlie);
         let bs = vec![alice.clone(), bob.clone(), charlie.clone()];
-        let cid = CurrencyIdentifier::default();
 
+        // Apia (Samoa) and Pago Pago (American Samoa)
         let a = Location {
-            lat: T::from_num(10),
-            lon: T::from_num(179),
+            lat: T::from_num(-13.8125),
+            lon: T::from_num(-171.75),
         };
         let b = Location {
-            lat: T::from_num(11),
-            lon: T::from_num(179),
+            lat: T::from_num(-14.25),
+            lon: T::from_num(-170.6875),
         };
         let loc = vec![a, b];
         assert!(
@@ -395,3 +1015,1667 @@ fn new_currency_near_dateline_fails() {
         );
     });
 }
+
+#[test]
+fn new_currency_across_dateline_next_to_existing_currency_works() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let charlie = AccountId::from(AccountKeyring::Charlie);
+        let bs = vec![alice.clone(), bob.clone(), charlie.clone()];
+
+        // Suva (Fiji)
+        let loc = vec![Location {
+            lat: T::from_num(-18.125),
+            lon: T::from_num(178.4375),
+        }];
+        assert_ok!(EncointerCurrencies::new_currency(
+            Origin::signed(alice.clone()),
+            loc,
+            bs.clone()
+        ));
+
+        // Apia (Samoa) is 1150km away from Suva and meets on the same ceremony date
+        let loc = vec![Location {
+            lat: T::from_num(-13.8125),
+            lon: T::from_num(-171.75),
+        }];
+        assert_ok!(EncointerCurrencies::new_currency(
+            Origin::signed(alice.clone()),
+            loc,
+            bs.clone()
+        ));
+
+        // but Pago Pago (American Samoa) meets almost 24h after Apia
+        let loc = vec![Location {
+            lat: T::from_num(-14.25),
+            lon: T::from_num(-170.6875),
+        }];
+        assert!(
+            EncointerCurrencies::new_currency(Origin::signed(alice.clone()), loc, bs).is_err()
+        );
+    });
+}
+
+fn square_region(lat: f64, lon: f64, size: f64) -> Vec<Location> {
+    vec![
+        loc(lat, lon),
+        loc(lat + size, lon),
+        loc(lat + size, lon + size),
+        loc(lat, lon + size),
+    ]
+}
+
+#[test]
+fn is_inside_region_works() {
+    let region = square_region(0.0, 0.0, 10.0);
+    assert!(EncointerCurrencies::is_inside_region(&loc(5.0, 5.0), &region));
+    assert!(EncointerCurrencies::is_inside_region(&loc(0.0, 5.0), &region));
+    assert!(EncointerCurrencies::is_inside_region(&loc(10.0, 10.0), &region));
+    assert!(!EncointerCurrencies::is_inside_region(&loc(11.0, 5.0), &region));
+    assert!(!EncointerCurrencies::is_inside_region(&loc(-0.5, -0.5), &region));
+    assert!(!EncointerCurrencies::is_inside_region(&loc(5.0, 5.0), &[]));
+
+    // concave
+    let region = vec![
+        loc(0.0, 0.0),
+        loc(10.0, 0.0),
+        loc(10.0, 10.0),
+        loc(5.0, 5.0),
+        loc(0.0, 10.0),
+    ];
+    assert!(EncointerCurrencies::is_inside_region(&loc(5.0, 2.0), &region));
+    assert!(!EncointerCurrencies::is_inside_region(&loc(5.0, 8.0), &region));
+}
+
+#[test]
+fn is_simple_polygon_works() {
+    assert!(EncointerCurrencies::is_simple_polygon(&square_region(0.0, 0.0, 1.0)));
+    // bowtie
+    let region = vec![loc(0.0, 0.0), loc(1.0, 1.0), loc(1.0, 0.0), loc(0.0, 1.0)];
+    assert!(!EncointerCurrencies::is_simple_polygon(&region));
+    // repeated vertex
+    let region = vec![loc(0.0, 0.0), loc(1.0, 0.0), loc(1.0, 0.0), loc(0.0, 1.0)];
+    assert!(!EncointerCurrencies::is_simple_polygon(&region));
+    // folding back onto itself
+    let region = vec![loc(0.0, 0.0), loc(2.0, 0.0), loc(1.0, 0.0)];
+    assert!(!EncointerCurrencies::is_simple_polygon(&region));
+}
+
+#[test]
+fn set_region_works() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        let region = square_region(0.0, 0.0, 3.0);
+        assert_ok!(EncointerCurrencies::set_region(
+            Origin::signed(AccountId::from(AccountKeyring::Bob)),
+            cid,
+            region.clone()
+        ));
+        assert_eq!(EncointerCurrencies::regions(&cid), region);
+    });
+}
+
+#[test]
+fn set_region_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(AccountId::from(AccountKeyring::Dave)),
+                cid,
+                square_region(0.0, 0.0, 3.0)
+            ),
+            Error::<TestRuntime>::NotBootstrapper
+        );
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                CurrencyIdentifier::default(),
+                square_region(0.0, 0.0, 3.0)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                cid,
+                vec![loc(0.0, 0.0), loc(3.0, 3.0), loc(3.0, 0.0), loc(0.0, 3.0)]
+            ),
+            Error::<TestRuntime>::InvalidRegion
+        );
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                cid,
+                vec![loc(0.0, 0.0), loc(3.0, 3.0)]
+            ),
+            Error::<TestRuntime>::InvalidRegion
+        );
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                cid,
+                square_region(0.0, 0.0, 1.5)
+            ),
+            Error::<TestRuntime>::LocationOutsideRegion
+        );
+    });
+}
+
+#[test]
+fn set_region_overlapping_other_region_fails() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        assert_ok!(EncointerCurrencies::set_region(
+            Origin::signed(alice.clone()),
+            cid,
+            square_region(0.0, 0.0, 3.0)
+        ));
+        let other = register_test_currency(vec![loc(1.0, 5.0)]);
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                other,
+                square_region(0.0, 2.0, 4.0)
+            ),
+            Error::<TestRuntime>::RegionOverlap
+        );
+        // enclosing the other region entirely
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                other,
+                square_region(-1.0, -1.0, 10.0)
+            ),
+            Error::<TestRuntime>::RegionOverlap
+        );
+        assert_ok!(EncointerCurrencies::set_region(
+            Origin::signed(alice.clone()),
+            other,
+            square_region(0.0, 4.0, 3.0)
+        ));
+    });
+}
+
+#[test]
+fn set_region_containing_foreign_location_fails() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        // the other currency has no region, but its location is inside the claimed one
+        register_test_currency(vec![loc(2.0, 2.5)]);
+        assert_noop!(
+            EncointerCurrencies::set_region(
+                Origin::signed(alice.clone()),
+                cid,
+                square_region(0.0, 0.0, 3.0)
+            ),
+            Error::<TestRuntime>::RegionContainsForeignLocation
+        );
+        assert_ok!(EncointerCurrencies::set_region(
+            Origin::signed(alice.clone()),
+            cid,
+            square_region(0.0, 0.0, 2.2)
+        ));
+    });
+}
+
+#[test]
+fn new_currency_inside_foreign_region_fails() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        assert_ok!(EncointerCurrencies::set_region(
+            Origin::signed(alice.clone()),
+            cid,
+            square_region(0.0, 0.0, 3.0)
+        ));
+        assert_err!(
+            EncointerCurrencies::new_currency(
+                Origin::signed(alice.clone()),
+                vec![loc(2.5, 2.5)],
+                test_bootstrappers()
+            ),
+            Error::<TestRuntime>::LocationInsideForeignRegion
+        );
+    });
+}
+
+#[test]
+fn validate_locations_works() {
+    ExtBuilder::build().execute_with(|| {
+        let loc = vec![loc(1.0, 1.0), loc(1.0, 2.0)];
+        assert_eq!(EncointerCurrencies::validate_locations(&loc), vec![]);
+        assert_eq!(EncointerCurrencies::validate_locations(&[]), vec![]);
+    });
+}
+
+#[test]
+fn validate_locations_reports_all_issues() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        let other = register_test_currency(vec![loc(20.0, 20.0)]);
+        assert_ok!(EncointerCurrencies::set_region(
+            Origin::signed(alice.clone()),
+            other,
+            square_region(19.0, 19.0, 2.0)
+        ));
+        let proposed = vec![
+            loc(10.0, 10.0),
+            Location {
+                lat: T::from_num(10),
+                lon: T::from_num(11) + T::from_bits(1),
+            },
+            loc(89.0, 0.0),
+            loc(1.0, 2.0),
+            loc(20.5, 20.5),
+            loc(10.0, 10.0),
+        ];
+        assert_eq!(
+            EncointerCurrencies::validate_locations(&proposed),
+            vec![
+                LocationValidationIssue {
+                    location_index: 0,
+                    reason: LocationRejectionReason::TooCloseToSibling(5)
+                },
+                LocationValidationIssue {
+                    location_index: 0,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToSibling(5)
+                },
+                LocationValidationIssue {
+                    location_index: 1,
+                    reason: LocationRejectionReason::InvalidCoordinate
+                },
+                LocationValidationIssue {
+                    location_index: 2,
+                    reason: LocationRejectionReason::NearPole
+                },
+                LocationValidationIssue {
+                    location_index: 3,
+                    reason: LocationRejectionReason::TooCloseToCurrency(cid, 1)
+                },
+                LocationValidationIssue {
+                    location_index: 3,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToCurrency(cid, 1)
+                },
+                LocationValidationIssue {
+                    location_index: 4,
+                    reason: LocationRejectionReason::InsideForeignRegion(other)
+                },
+                LocationValidationIssue {
+                    location_index: 5,
+                    reason: LocationRejectionReason::TooCloseToSibling(0)
+                },
+                LocationValidationIssue {
+                    location_index: 5,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToSibling(0)
+                },
+            ]
+        );
+    });
+}
+
+#[test]
+fn suggest_nearest_valid_location_works() {
+    ExtBuilder::build().execute_with(|| {
+        register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        let rejected = loc(1.0, 1.0);
+        assert!(!EncointerCurrencies::validate_locations(&[rejected]).is_empty());
+        let suggested = EncointerCurrencies::suggest_nearest_valid_location(&rejected).unwrap();
+        assert_eq!(EncointerCurrencies::validate_locations(&[suggested]), vec![]);
+        let d = EncointerCurrencies::haversine_distance(&rejected, &suggested).unwrap();
+        assert!(d > 0 && d <= 300);
+
+        // valid locations are returned unchanged
+        let valid = loc(5.0, 5.0);
+        assert_eq!(
+            EncointerCurrencies::suggest_nearest_valid_location(&valid),
+            Some(valid)
+        );
+    });
+}
+
+#[test]
+fn suggest_nearest_valid_location_gives_up_far_from_valid_locations() {
+    ExtBuilder::build().execute_with(|| {
+        assert_eq!(
+            EncointerCurrencies::suggest_nearest_valid_location(&loc(89.0, 0.0)),
+            None
+        );
+    });
+}
+
+#[test]
+fn location_report_works() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        let other = register_test_currency(vec![loc(3.0, 1.0), loc(1.0, 4.0)]);
+        let report = EncointerCurrencies::location_report(&cid).unwrap();
+        assert_eq!(report.min_solar_trip_time_s, MIN_SOLAR_TRIP_TIME_S);
+        assert_eq!(report.min_location_distance_m, 100);
+
+        assert_eq!(report.distances_m.len(), 2);
+        assert_eq!(report.distances_m[0][0], 0);
+        assert_eq!(report.distances_m[0][1], report.distances_m[1][0]);
+        assert_abs_diff_eq!(f64::from(report.distances_m[0][1]) * 0.001, 111.2, epsilon = 0.5);
+        assert_eq!(report.solar_trip_times_s[0][0], 0);
+        assert_eq!(
+            report.solar_trip_times_s[0][1],
+            EncointerCurrencies::solar_trip_time(&loc(1.0, 1.0), &loc(1.0, 2.0)).unwrap()
+        );
+
+        // (3, 1) is closer in solar time to (1, 1), (1, 4) is closer to (1, 2)
+        let closest = report.closest_foreign[0].unwrap();
+        assert_eq!((closest.cid, closest.location_index), (other, 0));
+        assert_eq!(
+            closest.distance_m,
+            EncointerCurrencies::haversine_distance(&loc(1.0, 1.0), &loc(3.0, 1.0)).unwrap()
+        );
+        let closest = report.closest_foreign[1].unwrap();
+        assert_eq!((closest.cid, closest.location_index), (other, 1));
+    });
+}
+
+#[test]
+fn location_report_without_foreign_locations_works() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        let report = EncointerCurrencies::location_report(&cid).unwrap();
+        assert_eq!(report.distances_m, vec![vec![0]]);
+        assert_eq!(report.closest_foreign, vec![None]);
+    });
+}
+
+#[test]
+fn new_currency_below_minimum_location_distance_fails() {
+    ExtBuilder::build().execute_with(|| {
+        MIN_LOCATION_DISTANCE_M.with(|v| *v.borrow_mut() = 200_000);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let a = loc(1.0, 1.0);
+        let b = loc(1.0, 2.0);
+        // 111km apart satisfies the solar trip time rule
+        assert!(
+            EncointerCurrencies::solar_trip_time(&a, &b).unwrap() >= MIN_SOLAR_TRIP_TIME_S
+        );
+        assert_eq!(
+            EncointerCurrencies::validate_locations(&[a, b]),
+            vec![
+                LocationValidationIssue {
+                    location_index: 0,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToSibling(1)
+                },
+                LocationValidationIssue {
+                    location_index: 1,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToSibling(0)
+                },
+            ]
+        );
+        System::set_block_number(1);
+        assert_err!(
+            EncointerCurrencies::new_currency(
+                Origin::signed(alice.clone()),
+                vec![a, b],
+                test_bootstrappers()
+            ),
+            Error::<TestRuntime>::MinimumLocationDistanceViolated
+        );
+        // one LocationRejected event per issue
+        assert_eq!(System::events().len(), 2);
+
+        let cid = register_test_currency(vec![a]);
+        assert_eq!(
+            EncointerCurrencies::validate_locations(&[b]),
+            vec![LocationValidationIssue {
+                location_index: 0,
+                reason: LocationRejectionReason::BelowMinimumDistanceToCurrency(cid, 0)
+            }]
+        );
+        MIN_LOCATION_DISTANCE_M.with(|v| *v.borrow_mut() = 100);
+        assert_eq!(EncointerCurrencies::validate_locations(&[b]), vec![]);
+    });
+}
+
+#[test]
+fn minimum_location_distance_is_precise_for_short_distances() {
+    ExtBuilder::build().execute_with(|| {
+        // ~200.15m apart
+        let a = loc(1.0, 1.0);
+        let b = loc(1.0018, 1.0);
+        MIN_LOCATION_DISTANCE_M.with(|v| *v.borrow_mut() = 195);
+        assert_eq!(EncointerCurrencies::validate_locations(&[a, b]), vec![]);
+        MIN_LOCATION_DISTANCE_M.with(|v| *v.borrow_mut() = 205);
+        assert_eq!(
+            EncointerCurrencies::validate_locations(&[a, b]),
+            vec![
+                LocationValidationIssue {
+                    location_index: 0,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToSibling(1)
+                },
+                LocationValidationIssue {
+                    location_index: 1,
+                    reason: LocationRejectionReason::BelowMinimumDistanceToSibling(0)
+                },
+            ]
+        );
+        MIN_LOCATION_DISTANCE_M.with(|v| *v.borrow_mut() = 100);
+    });
+}
+
+fn test_metadata() -> LocationMetadataType {
+    LocationMetadataType {
+        venue_name_utf8: b"Cafe Odeon".to_vec(),
+        description_utf8: b"ground floor, next to the bar".to_vec(),
+        accessibility: ACCESSIBLE_BY_WHEELCHAIR | ACCESSIBLE_BY_PUBLIC_TRANSPORT,
+        max_participants: 12,
+    }
+}
+
+#[test]
+fn set_location_metadata_works() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        assert_eq!(EncointerCurrencies::location_metadata(&cid, 1), None);
+        assert_ok!(EncointerCurrencies::set_location_metadata(
+            Origin::signed(AccountId::from(AccountKeyring::Charlie)),
+            cid,
+            1,
+            test_metadata()
+        ));
+        assert_eq!(
+            EncointerCurrencies::location_metadata(&cid, 1),
+            Some(test_metadata())
+        );
+        assert_eq!(EncointerCurrencies::location_metadata(&cid, 0), None);
+    });
+}
+
+#[test]
+fn set_location_metadata_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        assert_noop!(
+            EncointerCurrencies::set_location_metadata(
+                Origin::signed(AccountId::from(AccountKeyring::Dave)),
+                cid,
+                0,
+                test_metadata()
+            ),
+            Error::<TestRuntime>::NotBootstrapper
+        );
+        assert_noop!(
+            EncointerCurrencies::set_location_metadata(
+                Origin::signed(alice.clone()),
+                cid,
+                2,
+                test_metadata()
+            ),
+            Error::<TestRuntime>::LocationIndexOutOfBounds
+        );
+        let mut metadata = test_metadata();
+        metadata.venue_name_utf8 = vec![b'x'; MAX_VENUE_NAME_LEN + 1];
+        assert_noop!(
+            EncointerCurrencies::set_location_metadata(
+                Origin::signed(alice.clone()),
+                cid,
+                0,
+                metadata
+            ),
+            Error::<TestRuntime>::MetadataTooLong
+        );
+        let mut metadata = test_metadata();
+        metadata.description_utf8 = vec![b'x'; MAX_LOCATION_DESCRIPTION_LEN + 1];
+        assert_noop!(
+            EncointerCurrencies::set_location_metadata(
+                Origin::signed(alice.clone()),
+                cid,
+                0,
+                metadata
+            ),
+            Error::<TestRuntime>::MetadataTooLong
+        );
+    });
+}
+
+#[test]
+fn location_capacity_works() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0), loc(1.0, 3.0)]);
+        assert_eq!(EncointerCurrencies::location_capacity(&cid, 0), 10);
+        assert_eq!(EncointerCurrencies::total_capacity(&cid), 30);
+
+        // from metadata
+        assert_ok!(EncointerCurrencies::set_location_metadata(
+            Origin::signed(AccountId::from(AccountKeyring::Alice)),
+            cid,
+            1,
+            test_metadata()
+        ));
+        assert_eq!(EncointerCurrencies::location_capacity(&cid, 1), 12);
+        assert_eq!(EncointerCurrencies::total_capacity(&cid), 32);
+
+        assert_ok!(EncointerCurrencies::set_location_capacity(
+            Origin::signed(AccountId::from(AccountKeyring::Alice)),
+            cid,
+            2,
+            50
+        ));
+        assert_eq!(EncointerCurrencies::location_capacity(&cid, 2), 50);
+        assert_eq!(EncointerCurrencies::total_capacity(&cid), 72);
+
+        // 0 resets to default, but keeps the venue metadata
+        assert_ok!(EncointerCurrencies::set_location_capacity(
+            Origin::signed(AccountId::from(AccountKeyring::Alice)),
+            cid,
+            1,
+            0
+        ));
+        assert_eq!(EncointerCurrencies::location_capacity(&cid, 1), 10);
+        assert_eq!(
+            EncointerCurrencies::location_metadata(&cid, 1).unwrap().venue_name_utf8,
+            test_metadata().venue_name_utf8
+        );
+
+        assert_eq!(EncointerCurrencies::location_capacity(&cid, 3), 0);
+        assert_eq!(
+            EncointerCurrencies::total_capacity(&CurrencyIdentifier::default()),
+            0
+        );
+    });
+}
+
+#[test]
+fn set_location_capacity_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_noop!(
+            EncointerCurrencies::set_location_capacity(
+                Origin::signed(AccountId::from(AccountKeyring::Dave)),
+                cid,
+                0,
+                20
+            ),
+            Error::<TestRuntime>::NotBootstrapper
+        );
+        assert_noop!(
+            EncointerCurrencies::set_location_capacity(
+                Origin::signed(AccountId::from(AccountKeyring::Alice)),
+                cid,
+                1,
+                20
+            ),
+            Error::<TestRuntime>::LocationIndexOutOfBounds
+        );
+    });
+}
+
+fn set_balance(cid: &CurrencyIdentifier, who: &AccountId, amount: f64) {
+    <crate::Balance<TestRuntime>>::insert(
+        cid,
+        who,
+        BalanceEntry {
+            principal: BalanceType::from_num(amount),
+            last_update: System::block_number(),
+        },
+    );
+}
+
+#[test]
+fn transfer_works() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        set_balance(&cid, &alice, 100.0);
+        assert_ok!(EncointerCurrencies::transfer(
+            Origin::signed(alice.clone()),
+            cid,
+            bob.clone(),
+            BalanceType::from_num(40)
+        ));
+        assert_eq!(EncointerCurrencies::balance(&cid, &alice), BalanceType::from_num(60));
+        assert_eq!(EncointerCurrencies::balance(&cid, &bob), BalanceType::from_num(40));
+        assert_eq!(EncointerCurrencies::balance_entry(&cid, &bob).last_update, 1);
+
+        // to self
+        assert_ok!(EncointerCurrencies::transfer(
+            Origin::signed(alice.clone()),
+            cid,
+            alice.clone(),
+            BalanceType::from_num(60)
+        ));
+        assert_eq!(EncointerCurrencies::balance(&cid, &alice), BalanceType::from_num(60));
+    });
+}
+
+#[test]
+fn transfer_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        set_balance(&cid, &alice, 100.0);
+        assert_noop!(
+            EncointerCurrencies::transfer(
+                Origin::signed(alice.clone()),
+                cid,
+                bob.clone(),
+                BalanceType::from_num(101)
+            ),
+            Error::<TestRuntime>::BalanceTooLow
+        );
+        assert_noop!(
+            EncointerCurrencies::transfer(
+                Origin::signed(alice.clone()),
+                cid,
+                bob.clone(),
+                BalanceType::from_num(-1)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+        assert_noop!(
+            EncointerCurrencies::transfer(
+                Origin::signed(alice.clone()),
+                cid,
+                bob.clone(),
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+        assert_noop!(
+            EncointerCurrencies::transfer(
+                Origin::signed(alice.clone()),
+                CurrencyIdentifier::default(),
+                bob.clone(),
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+    });
+}
+
+#[test]
+fn balance_applies_demurrage() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        set_balance(&cid, &alice, 100.0);
+        let demurrage: f64 = EncointerCurrencies::currency_properties(&cid)
+            .demurrage_per_block
+            .to_num();
+
+        System::set_block_number(1_000_001);
+        let expected = 100.0 * (-demurrage * 1_000_000.0).exp();
+        assert!(expected < 99.0);
+        assert_abs_diff_eq!(
+            EncointerCurrencies::balance(&cid, &alice).to_num::<f64>(),
+            expected,
+            epsilon = 1e-6
+        );
+        // the entry is only updated on write
+        assert_eq!(EncointerCurrencies::balance_entry(&cid, &alice).last_update, 1);
+
+        assert_ok!(EncointerCurrencies::transfer(
+            Origin::signed(alice.clone()),
+            cid,
+            bob.clone(),
+            BalanceType::from_num(50)
+        ));
+        let entry = EncointerCurrencies::balance_entry(&cid, &alice);
+        assert_eq!(entry.last_update, 1_000_001);
+        assert_abs_diff_eq!(entry.principal.to_num::<f64>(), expected - 50.0, epsilon = 1e-6);
+        assert_eq!(EncointerCurrencies::balance(&cid, &bob), BalanceType::from_num(50));
+    });
+}
+
+#[test]
+fn apply_demurrage_works() {
+    let demurrage = Demurrage::from_num(0.000_001);
+    let balance = BalanceType::from_num(1000);
+    for elapsed in [0u32, 1, 10, 1_000, 100_000, 1_000_000, 10_000_000].iter() {
+        let expected = 1000.0 * (-0.000_001 * f64::from(*elapsed)).exp();
+        assert_abs_diff_eq!(
+            apply_demurrage(balance, demurrage, *elapsed).to_num::<f64>(),
+            expected,
+            epsilon = 1e-7
+        );
+    }
+    // decays to zero instead of failing
+    assert_eq!(
+        apply_demurrage(balance, demurrage, std::u32::MAX),
+        BalanceType::from_num(0)
+    );
+    assert_eq!(
+        apply_demurrage(balance, Demurrage::from_num(0), 1_000_000),
+        balance
+    );
+    assert_eq!(
+        apply_demurrage(balance, Demurrage::from_num(-0.1), 1_000_000),
+        balance
+    );
+}
+
+proptest! {
+    #[test]
+    fn apply_demurrage_is_accurate(
+        balance in 0.0f64..1_000_000_000.0,
+        demurrage in 0.0f64..0.001,
+        elapsed in 0u32..10_000_000,
+    ) {
+        let b = BalanceType::from_num(balance);
+        let d = Demurrage::from_num(demurrage);
+        let expected = b.to_num::<f64>() * (-d.to_num::<f64>() * f64::from(elapsed)).exp();
+        let result = apply_demurrage(b, d, elapsed).to_num::<f64>();
+        prop_assert!((result - expected).abs() <= 1e-6 + 1e-12 * balance);
+        prop_assert!(result <= balance);
+    }
+}
+
+#[test]
+fn demurrage_from_half_life_works() {
+    let demurrage = demurrage_from_half_life(1_000).unwrap();
+    assert_abs_diff_eq!(demurrage.to_num::<f64>(), 2f64.ln() / 1_000.0, epsilon = 1e-15);
+    assert_abs_diff_eq!(
+        apply_demurrage(BalanceType::from_num(100), demurrage, 1_000).to_num::<f64>(),
+        50.0,
+        epsilon = 1e-9
+    );
+    assert_eq!(demurrage_from_half_life(0), None);
+}
+
+#[test]
+fn half_life_from_demurrage_works() {
+    for blocks in [1u64, 1_000, 6_151_680, 1_000_000_000].iter() {
+        assert_eq!(
+            half_life_from_demurrage(demurrage_from_half_life(*blocks).unwrap()),
+            Some(*blocks)
+        );
+    }
+    assert_eq!(half_life_from_demurrage(Demurrage::from_num(0)), None);
+    assert_eq!(half_life_from_demurrage(Demurrage::from_num(-0.1)), None);
+    assert_eq!(half_life_from_demurrage(Demurrage::from_bits(1)), None);
+    // the former hardcoded default demurrage halves balances every 6'151'680 blocks
+    assert_eq!(
+        demurrage_from_half_life(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS),
+        Some(Demurrage::from_bits(0x0000000000000000000001E3F0A8A973_i128))
+    );
+}
+
+#[test]
+fn currency_properties_from_half_life_days_works() {
+    // 356 days at 5s blocks match the default half-life
+    let properties =
+        CurrencyPropertiesType::from_half_life_days(b"Leu".to_vec(), 356, 5_000).unwrap();
+    assert_eq!(properties.name_utf8, b"Leu".to_vec());
+    assert_eq!(
+        properties.demurrage_per_block,
+        demurrage_from_half_life(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS).unwrap()
+    );
+    assert_eq!(blocks_per_days(1, 6_000), Some(14_400));
+    assert_eq!(blocks_per_days(0, 5_000), None);
+    assert_eq!(blocks_per_days(1, 0), None);
+    assert_eq!(
+        CurrencyPropertiesType::from_half_life_days(b"Leu".to_vec(), 0, 5_000),
+        None
+    );
+}
+
+#[test]
+fn currency_properties_from_annual_rate_works() {
+    let blocks_per_year = blocks_per_days(365, 5_000).unwrap();
+    assert_eq!(blocks_per_year, 6_307_200);
+    // losing half per year is a half-life of one year
+    let properties =
+        CurrencyPropertiesType::from_annual_rate(b"Leu".to_vec(), Percent::from_percent(50), 5_000)
+            .unwrap();
+    assert_abs_diff_eq!(
+        properties.demurrage_per_block.to_num::<f64>(),
+        demurrage_from_half_life(blocks_per_year).unwrap().to_num::<f64>(),
+        epsilon = 1e-18
+    );
+    for percent in [1u8, 10, 50, 99].iter() {
+        let demurrage =
+            demurrage_from_annual_rate(Percent::from_percent(*percent), 5_000).unwrap();
+        assert_abs_diff_eq!(
+            apply_demurrage(BalanceType::from_num(100), demurrage, blocks_per_year as u32)
+                .to_num::<f64>(),
+            100.0 - f64::from(*percent),
+            epsilon = 1e-6
+        );
+    }
+    assert_eq!(
+        demurrage_from_annual_rate(Percent::from_percent(0), 5_000),
+        Some(Demurrage::from_num(0))
+    );
+    assert_eq!(demurrage_from_annual_rate(Percent::from_percent(100), 5_000), None);
+}
+
+#[test]
+fn demurrage_per_block_works() {
+    ExtBuilder::build().execute_with(|| {
+        let cid = register_test_currency(vec![loc(1.0, 1.0), loc(1.0, 2.0)]);
+        assert_eq!(
+            half_life_from_demurrage(EncointerCurrencies::demurrage_per_block(&cid)),
+            Some(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS)
+        );
+        assert_eq!(
+            EncointerCurrencies::demurrage_per_block(&CurrencyIdentifier::default()),
+            Demurrage::from_num(0)
+        );
+        assert_eq!(
+            EncointerCurrencies::demurrage_from_half_life_days(356),
+            EncointerCurrencies::demurrage_per_block(&cid).into()
+        );
+        assert_abs_diff_eq!(
+            EncointerCurrencies::demurrage_from_annual_rate(Percent::from_percent(50))
+                .unwrap()
+                .to_num::<f64>(),
+            demurrage_from_half_life(6_307_200).unwrap().to_num::<f64>(),
+            epsilon = 1e-18
+        );
+    });
+}
+
+#[test]
+fn issue_works() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        set_balance(&cid, &alice, 10.0);
+        assert_eq!(
+            <EncointerCurrencies as CurrencyIssuer<AccountId>>::nominal_income(&cid),
+            DEFAULT_NOMINAL_INCOME
+        );
+        assert_ok!(<EncointerCurrencies as CurrencyIssuer<AccountId>>::issue(
+            &cid,
+            &[alice.clone(), bob.clone(), bob.clone()],
+            BalanceType::from_num(1.5)
+        ));
+        assert_eq!(EncointerCurrencies::balance(&cid, &alice), BalanceType::from_num(11.5));
+        assert_eq!(EncointerCurrencies::balance(&cid, &bob), BalanceType::from_num(3));
+        assert_eq!(EncointerCurrencies::total_issuance(&cid), BalanceType::from_num(4.5));
+    });
+}
+
+#[test]
+fn issue_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_noop!(
+            <EncointerCurrencies as CurrencyIssuer<AccountId>>::issue(
+                &CurrencyIdentifier::default(),
+                &[alice.clone()],
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+        assert_noop!(
+            <EncointerCurrencies as CurrencyIssuer<AccountId>>::issue(
+                &cid,
+                &[alice.clone()],
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+        // bob's overflow leaves alice's balance untouched
+        set_balance(&cid, &bob, 1.5 * 2f64.powi(62));
+        assert_noop!(
+            <EncointerCurrencies as CurrencyIssuer<AccountId>>::issue(
+                &cid,
+                &[alice.clone(), bob.clone()],
+                BalanceType::from_num(2f64.powi(61))
+            ),
+            Error::<TestRuntime>::BalanceOverflow
+        );
+        assert_eq!(EncointerCurrencies::balance(&cid, &alice), BalanceType::from_num(0));
+    });
+}
+
+#[test]
+fn set_nominal_income_works() {
+    ExtBuilder::build().execute_with(|| {
+        let bs = test_bootstrappers();
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_ok!(EncointerCurrencies::set_nominal_income(
+            Origin::signed(bs[1].clone()),
+            cid,
+            BalanceType::from_num(22)
+        ));
+        assert_eq!(
+            EncointerCurrencies::currency_properties(&cid).nominal_income,
+            BalanceType::from_num(22)
+        );
+        assert_noop!(
+            EncointerCurrencies::set_nominal_income(
+                Origin::signed(AccountId::from(AccountKeyring::Dave)),
+                cid,
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::NotBootstrapper
+        );
+        assert_noop!(
+            EncointerCurrencies::set_nominal_income(
+                Origin::signed(bs[1].clone()),
+                cid,
+                BalanceType::from_num(-1)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+    });
+}
+
+#[test]
+fn total_issuance_decays_with_balances() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_ok!(<EncointerCurrencies as CurrencyIssuer<AccountId>>::issue(
+            &cid,
+            &[alice.clone(), bob.clone()],
+            BalanceType::from_num(100)
+        ));
+        assert_eq!(EncointerCurrencies::total_issuance(&cid), BalanceType::from_num(200));
+
+        System::set_block_number(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS + 1);
+        assert_abs_diff_eq!(
+            EncointerCurrencies::total_issuance(&cid).to_num::<f64>(),
+            100.0,
+            epsilon = 1e-6
+        );
+        assert_ok!(EncointerCurrencies::transfer(
+            Origin::signed(alice.clone()),
+            cid,
+            bob.clone(),
+            BalanceType::from_num(20)
+        ));
+        assert_ok!(<EncointerCurrencies as CurrencyIssuer<AccountId>>::issue(
+            &cid,
+            &[alice.clone()],
+            BalanceType::from_num(10)
+        ));
+        let sum =
+            EncointerCurrencies::balance(&cid, &alice) + EncointerCurrencies::balance(&cid, &bob);
+        assert_abs_diff_eq!(
+            EncointerCurrencies::total_issuance(&cid).to_num::<f64>(),
+            sum.to_num::<f64>(),
+            epsilon = 1e-9
+        );
+        assert_abs_diff_eq!(sum.to_num::<f64>(), 110.0, epsilon = 1e-6);
+        assert_eq!(
+            EncointerCurrencies::total_issuance_entry(&cid).last_update,
+            DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS + 1
+        );
+    });
+}
+
+#[test]
+fn multi_currency_works() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid,
+            &alice,
+            BalanceType::from_num(50)
+        ));
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::transfer(
+            cid,
+            &alice,
+            &bob,
+            BalanceType::from_num(20)
+        ));
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::withdraw(
+            cid,
+            &bob,
+            BalanceType::from_num(5)
+        ));
+        assert_eq!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::free_balance(cid, &alice),
+            BalanceType::from_num(30)
+        );
+        assert_eq!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::free_balance(cid, &bob),
+            BalanceType::from_num(15)
+        );
+        assert_eq!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::total_issuance(cid),
+            BalanceType::from_num(45)
+        );
+        // registration, issue, transfer and burn
+        assert_eq!(System::events().len(), 4);
+    });
+}
+
+#[test]
+fn multi_currency_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        set_balance(&cid, &alice, 10.0);
+        assert_noop!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::withdraw(
+                cid,
+                &alice,
+                BalanceType::from_num(11)
+            ),
+            Error::<TestRuntime>::BalanceTooLow
+        );
+        assert_noop!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+                cid,
+                &alice,
+                BalanceType::from_num(-1)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+        assert_noop!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::transfer(
+                CurrencyIdentifier::default(),
+                &alice,
+                &bob,
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+    });
+}
+
+#[test]
+fn set_fee_conversion_rate_works() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_eq!(EncointerCurrencies::fee_conversion_rate(&cid), None);
+        assert_ok!(EncointerCurrencies::set_fee_conversion_rate(
+            Origin::signed(alice.clone()),
+            cid,
+            BalanceType::from_num(0.5)
+        ));
+        assert_eq!(
+            EncointerCurrencies::fee_conversion_rate(&cid),
+            Some(BalanceType::from_num(0.5))
+        );
+        assert_noop!(
+            EncointerCurrencies::set_fee_conversion_rate(
+                Origin::signed(bob.clone()),
+                cid,
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::NotCurrencyMaster
+        );
+        assert_noop!(
+            EncointerCurrencies::set_fee_conversion_rate(
+                Origin::signed(alice.clone()),
+                CurrencyIdentifier::default(),
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+        assert_noop!(
+            EncointerCurrencies::set_fee_conversion_rate(
+                Origin::signed(alice.clone()),
+                cid,
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+    });
+}
+
+#[test]
+fn charge_community_currency_works() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        let charge = |cid| ChargeCommunityCurrency::<TestRuntime, _>::new(cid, ChargeNativeFee);
+        let info = DispatchInfo {
+            weight: 90,
+            pays_fee: true,
+            ..Default::default()
+        };
+        // no rate yet
+        assert!(charge(Some(cid)).validate(&bob, &(), info, 10).is_err());
+        assert_ok!(EncointerCurrencies::set_fee_conversion_rate(
+            Origin::signed(alice.clone()),
+            cid,
+            BalanceType::from_num(0.5)
+        ));
+        // (base 10 + byte 1 * 10 + weight 90) * 0.5
+        assert_eq!(
+            ChargeCommunityCurrency::<TestRuntime, ChargeNativeFee>::compute_fee(&cid, info, 10),
+            Ok(BalanceType::from_num(55))
+        );
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid,
+            &bob,
+            BalanceType::from_num(60)
+        ));
+        // locked funds can't pay fees
+        const OFFER: LockIdentifier = *b"offer   ";
+        assert_ok!(<EncointerCurrencies as MultiLockableCurrency<AccountId>>::set_lock(
+            OFFER,
+            cid,
+            &bob,
+            BalanceType::from_num(10)
+        ));
+        assert!(charge(Some(cid)).validate(&bob, &(), info, 10).is_err());
+        <EncointerCurrencies as MultiLockableCurrency<AccountId>>::remove_lock(OFFER, cid, &bob);
+        // priority is the fee in native units
+        assert_eq!(charge(Some(cid)).validate(&bob, &(), info, 10).unwrap().priority, 110);
+        let events = System::events().len();
+        assert_ok!(charge(Some(cid)).pre_dispatch(&bob, &(), info, 10));
+        assert_eq!(System::events().len(), events + 1);
+        assert_eq!(EncointerCurrencies::balance(&cid, &bob), BalanceType::from_num(5));
+        assert_eq!(EncointerCurrencies::total_issuance(&cid), BalanceType::from_num(5));
+        assert_eq!(
+            COMMUNITY_FEES_PAID.with(|v| v.borrow().clone()),
+            vec![(cid, BalanceType::from_num(55))]
+        );
+        assert!(charge(Some(cid)).pre_dispatch(&bob, &(), info, 10).is_err());
+        // the native fee is only charged without a currency
+        assert_eq!(NATIVE_FEES_CHARGED.with(|v| *v.borrow()), 0);
+        assert_ok!(charge(None).pre_dispatch(&bob, &(), info, 10));
+        assert_eq!(NATIVE_FEES_CHARGED.with(|v| *v.borrow()), 1);
+        assert_eq!(EncointerCurrencies::balance(&cid, &bob), BalanceType::from_num(5));
+    });
+}
+
+#[test]
+fn charge_community_currency_is_free_for_calls_without_fee() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_ok!(EncointerCurrencies::set_fee_conversion_rate(
+            Origin::signed(alice.clone()),
+            cid,
+            BalanceType::from_num(0.5)
+        ));
+        let info = DispatchInfo {
+            weight: 90,
+            pays_fee: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            ChargeCommunityCurrency::<TestRuntime, ChargeNativeFee>::compute_fee(&cid, info, 10),
+            Ok(BalanceType::from_num(0))
+        );
+        // bob holds nothing and can still submit
+        assert_ok!(ChargeCommunityCurrency::<TestRuntime, _>::new(Some(cid), ChargeNativeFee)
+            .pre_dispatch(&bob, &(), info, 10));
+        assert_eq!(COMMUNITY_FEES_PAID.with(|v| v.borrow().len()), 0);
+        assert_eq!(NATIVE_FEES_CHARGED.with(|v| *v.borrow()), 0);
+    });
+}
+
+#[test]
+fn set_exchange_rate_works() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid1 = register_test_currency(vec![loc(1.0, 1.0)]);
+        let cid2 = register_test_currency(vec![loc(1.0, 2.0)]);
+        assert_eq!(EncointerCurrencies::exchange_rate(&cid1, &cid2), None);
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid2,
+            BalanceType::from_num(2.5)
+        ));
+        assert_eq!(
+            EncointerCurrencies::exchange_rate(&cid1, &cid2),
+            Some(BalanceType::from_num(2.5))
+        );
+        // rates are directed
+        assert_eq!(EncointerCurrencies::exchange_rate(&cid2, &cid1), None);
+        // the reverse rate may leave a spread
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid2,
+            cid1,
+            BalanceType::from_num(0.375)
+        ));
+    });
+}
+
+#[test]
+fn set_exchange_rate_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid1 = register_test_currency(vec![loc(1.0, 1.0)]);
+        let cid2 = register_test_currency(vec![loc(1.0, 2.0)]);
+        assert_noop!(
+            EncointerCurrencies::set_exchange_rate(
+                Origin::signed(bob.clone()),
+                cid1,
+                cid2,
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::NotCurrencyMaster
+        );
+        assert_noop!(
+            EncointerCurrencies::set_exchange_rate(
+                Origin::signed(alice.clone()),
+                cid1,
+                CurrencyIdentifier::default(),
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+        assert_noop!(
+            EncointerCurrencies::set_exchange_rate(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid1,
+                BalanceType::from_num(1)
+            ),
+            Error::<TestRuntime>::InvalidCurrencyPair
+        );
+        assert_noop!(
+            EncointerCurrencies::set_exchange_rate(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid2,
+                BalanceType::from_num(-1)
+            ),
+            Error::<TestRuntime>::InvalidAmount
+        );
+        // converting back and forth may not gain value, whichever rate is set first
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid2,
+            BalanceType::from_num(2.5)
+        ));
+        assert_noop!(
+            EncointerCurrencies::set_exchange_rate(
+                Origin::signed(alice.clone()),
+                cid2,
+                cid1,
+                BalanceType::from_num(0.5)
+            ),
+            Error::<TestRuntime>::InconsistentExchangeRate
+        );
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid2,
+            cid1,
+            BalanceType::from_num(0.25)
+        ));
+        assert_noop!(
+            EncointerCurrencies::set_exchange_rate(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid2,
+                BalanceType::from_num(5)
+            ),
+            Error::<TestRuntime>::InconsistentExchangeRate
+        );
+    });
+}
+
+#[test]
+fn convert_works() {
+    ExtBuilder::build().execute_with(|| {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid1 = register_test_currency(vec![loc(1.0, 1.0)]);
+        let cid2 = register_test_currency(vec![loc(1.0, 2.0)]);
+        let amount = BalanceType::from_num(4);
+        assert_eq!(EncointerCurrencies::convert(amount, &cid1, &cid1), Some(amount));
+        assert_eq!(EncointerCurrencies::convert(amount, &cid1, &cid2), None);
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid2,
+            BalanceType::from_num(2.5)
+        ));
+        assert_eq!(
+            EncointerCurrencies::convert(amount, &cid1, &cid2),
+            Some(BalanceType::from_num(10))
+        );
+        assert_eq!(EncointerCurrencies::convert(amount, &cid2, &cid1), None);
+        assert_eq!(
+            EncointerCurrencies::convert(BalanceType::max_value(), &cid1, &cid2),
+            None
+        );
+    });
+}
+
+#[test]
+fn transfer_converted_works() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid1 = register_test_currency(vec![loc(1.0, 1.0)]);
+        let cid2 = register_test_currency(vec![loc(1.0, 2.0)]);
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid2,
+            BalanceType::from_num(2.5)
+        ));
+        let reserve = EncointerCurrencies::exchange_reserve_account();
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid1,
+            &alice,
+            BalanceType::from_num(10)
+        ));
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid2,
+            &reserve,
+            BalanceType::from_num(12)
+        ));
+        assert_ok!(EncointerCurrencies::transfer_converted(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid2,
+            bob.clone(),
+            BalanceType::from_num(4),
+            BalanceType::from_num(10)
+        ));
+        assert_eq!(EncointerCurrencies::balance(&cid1, &alice), BalanceType::from_num(6));
+        assert_eq!(EncointerCurrencies::balance(&cid2, &bob), BalanceType::from_num(10));
+        assert_eq!(EncointerCurrencies::balance(&cid1, &reserve), BalanceType::from_num(4));
+        assert_eq!(EncointerCurrencies::balance(&cid2, &reserve), BalanceType::from_num(2));
+        // the reserve settles the conversion, so the money supplies don't change
+        assert_eq!(EncointerCurrencies::total_issuance(&cid1), BalanceType::from_num(10));
+        assert_eq!(EncointerCurrencies::total_issuance(&cid2), BalanceType::from_num(12));
+
+        // same currency is a plain transfer
+        assert_ok!(EncointerCurrencies::transfer_converted(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid1,
+            bob.clone(),
+            BalanceType::from_num(1),
+            BalanceType::from_num(1)
+        ));
+        assert_eq!(EncointerCurrencies::balance(&cid1, &bob), BalanceType::from_num(1));
+        assert_eq!(EncointerCurrencies::total_issuance(&cid1), BalanceType::from_num(10));
+    });
+}
+
+#[test]
+fn transfer_converted_fails_for_invalid_input() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid1 = register_test_currency(vec![loc(1.0, 1.0)]);
+        let cid2 = register_test_currency(vec![loc(1.0, 2.0)]);
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid1,
+            &alice,
+            BalanceType::from_num(10)
+        ));
+        assert_noop!(
+            EncointerCurrencies::transfer_converted(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid2,
+                bob.clone(),
+                BalanceType::from_num(4),
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::NoExchangeRate
+        );
+        assert_ok!(EncointerCurrencies::set_exchange_rate(
+            Origin::signed(alice.clone()),
+            cid1,
+            cid2,
+            BalanceType::from_num(2.5)
+        ));
+        assert_noop!(
+            EncointerCurrencies::transfer_converted(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid2,
+                bob.clone(),
+                BalanceType::from_num(4),
+                BalanceType::from_num(10.1)
+            ),
+            Error::<TestRuntime>::SlippageExceeded
+        );
+        assert_noop!(
+            EncointerCurrencies::transfer_converted(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid2,
+                bob.clone(),
+                BalanceType::from_num(11),
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::BalanceTooLow
+        );
+        // the reserve can't pay 10 of cid2
+        assert_noop!(
+            EncointerCurrencies::transfer_converted(
+                Origin::signed(alice.clone()),
+                cid1,
+                cid2,
+                bob.clone(),
+                BalanceType::from_num(4),
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::InsufficientExchangeReserve
+        );
+        assert_noop!(
+            EncointerCurrencies::transfer_converted(
+                Origin::signed(alice.clone()),
+                cid1,
+                CurrencyIdentifier::default(),
+                bob.clone(),
+                BalanceType::from_num(1),
+                BalanceType::from_num(0)
+            ),
+            Error::<TestRuntime>::CurrencyNotRegistered
+        );
+    });
+}
+
+#[test]
+fn reserve_and_unreserve_work() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid,
+            &alice,
+            BalanceType::from_num(100)
+        ));
+        assert_ok!(<EncointerCurrencies as MultiReservableCurrency<AccountId>>::reserve(
+            cid,
+            &alice,
+            BalanceType::from_num(40)
+        ));
+        assert_eq!(EncointerCurrencies::balance(&cid, &alice), BalanceType::from_num(60));
+        assert_eq!(
+            EncointerCurrencies::reserved_balance(&cid, &alice),
+            BalanceType::from_num(40)
+        );
+        assert_noop!(
+            <EncointerCurrencies as MultiReservableCurrency<AccountId>>::reserve(
+                cid,
+                &alice,
+                BalanceType::from_num(61)
+            ),
+            Error::<TestRuntime>::BalanceTooLow
+        );
+
+        // reserved balances decay like free ones
+        System::set_block_number(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS + 1);
+        assert_abs_diff_eq!(
+            EncointerCurrencies::reserved_balance(&cid, &alice).to_num::<f64>(),
+            20.0,
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(
+            EncointerCurrencies::total_issuance(&cid).to_num::<f64>(),
+            50.0,
+            epsilon = 1e-6
+        );
+        let remaining = <EncointerCurrencies as MultiReservableCurrency<AccountId>>::unreserve(
+            cid,
+            &alice,
+            BalanceType::from_num(25),
+        );
+        assert_abs_diff_eq!(remaining.to_num::<f64>(), 5.0, epsilon = 1e-6);
+        assert_eq!(
+            EncointerCurrencies::reserved_balance(&cid, &alice),
+            BalanceType::from_num(0)
+        );
+        assert_abs_diff_eq!(
+            EncointerCurrencies::balance(&cid, &alice).to_num::<f64>(),
+            50.0,
+            epsilon = 1e-6
+        );
+    });
+}
+
+#[test]
+fn locks_restrict_withdrawals() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        const OFFER: LockIdentifier = *b"offer   ";
+        const ESCROW: LockIdentifier = *b"escrow  ";
+        assert_ok!(<EncointerCurrencies as MultiCurrency<AccountId>>::deposit(
+            cid,
+            &alice,
+            BalanceType::from_num(100)
+        ));
+        assert_ok!(<EncointerCurrencies as MultiLockableCurrency<AccountId>>::set_lock(
+            OFFER,
+            cid,
+            &alice,
+            BalanceType::from_num(30)
+        ));
+        assert_ok!(<EncointerCurrencies as MultiLockableCurrency<AccountId>>::set_lock(
+            ESCROW,
+            cid,
+            &alice,
+            BalanceType::from_num(60)
+        ));
+        // locks overlap
+        assert_eq!(
+            EncointerCurrencies::frozen_balance(&cid, &alice),
+            BalanceType::from_num(60)
+        );
+        assert_noop!(
+            EncointerCurrencies::transfer(
+                Origin::signed(alice.clone()),
+                cid,
+                bob.clone(),
+                BalanceType::from_num(41)
+            ),
+            Error::<TestRuntime>::BalanceLocked
+        );
+        assert_noop!(
+            <EncointerCurrencies as MultiCurrency<AccountId>>::withdraw(
+                cid,
+                &alice,
+                BalanceType::from_num(41)
+            ),
+            Error::<TestRuntime>::BalanceLocked
+        );
+        assert_noop!(
+            <EncointerCurrencies as MultiReservableCurrency<AccountId>>::reserve(
+                cid,
+                &alice,
+                BalanceType::from_num(41)
+            ),
+            Error::<TestRuntime>::BalanceLocked
+        );
+
+        // locks decay along with the balance
+        System::set_block_number(DEFAULT_DEMURRAGE_HALF_LIFE_BLOCKS + 1);
+        assert_abs_diff_eq!(
+            EncointerCurrencies::frozen_balance(&cid, &alice).to_num::<f64>(),
+            30.0,
+            epsilon = 1e-6
+        );
+        assert_ok!(EncointerCurrencies::transfer(
+            Origin::signed(alice.clone()),
+            cid,
+            bob.clone(),
+            BalanceType::from_num(19)
+        ));
+
+        <EncointerCurrencies as MultiLockableCurrency<AccountId>>::remove_lock(
+            ESCROW, cid, &alice,
+        );
+        assert_abs_diff_eq!(
+            EncointerCurrencies::frozen_balance(&cid, &alice).to_num::<f64>(),
+            15.0,
+            epsilon = 1e-6
+        );
+        // replacing a lock resets its amount
+        assert_ok!(<EncointerCurrencies as MultiLockableCurrency<AccountId>>::set_lock(
+            OFFER,
+            cid,
+            &alice,
+            BalanceType::from_num(5)
+        ));
+        assert_eq!(
+            EncointerCurrencies::frozen_balance(&cid, &alice),
+            BalanceType::from_num(5)
+        );
+        assert_eq!(EncointerCurrencies::locks(&cid, &alice).len(), 1);
+        // removing the last lock cleans up storage
+        <EncointerCurrencies as MultiLockableCurrency<AccountId>>::remove_lock(OFFER, cid, &alice);
+        assert!(!<Locks<TestRuntime>>::contains_key(&cid, &alice));
+    });
+}
+
+#[test]
+fn set_lock_fails_beyond_max_locks() {
+    ExtBuilder::build().execute_with(|| {
+        System::set_block_number(1);
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let cid = register_test_currency(vec![loc(1.0, 1.0)]);
+        let set_lock = |i: usize| {
+            let mut lock_id: LockIdentifier = *b"lock    ";
+            lock_id[7] = i as u8;
+            <EncointerCurrencies as MultiLockableCurrency<AccountId>>::set_lock(
+                lock_id,
+                cid,
+                &alice,
+                BalanceType::from_num(1),
+            )
+        };
+        for i in 0..MAX_LOCKS {
+            assert_ok!(set_lock(i));
+        }
+        assert_noop!(set_lock(MAX_LOCKS), Error::<TestRuntime>::TooManyLocks);
+        // existing locks can still be replaced
+        assert_ok!(set_lock(0));
+        assert_eq!(EncointerCurrencies::locks(&cid, &alice).len(), MAX_LOCKS);
+    });
+}